@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use guest_server::{HashRouter, HttpMethod, HttpResponse, Route, Router, TrieRouter};
+
+/// Registers `count` routes shaped like a typical REST API.
+fn fill<R: Router>(router: &mut R, count: usize, params: bool) {
+    for i in 0..count {
+        let path = if params {
+            format!("/api/resource{}/:id/items/:item", i)
+        } else {
+            format!("/api/resource{}/items", i)
+        };
+        router.add(
+            HttpMethod::GET,
+            &path,
+            Route::new(Arc::new(|_| HttpResponse::new(200, None))),
+        );
+    }
+}
+
+fn lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lookup");
+    for count in [10, 100, 1_000, 10_000] {
+        let mut trie = TrieRouter::new();
+        fill(&mut trie, count, true);
+        let path = format!("/api/resource{}/42/items/7", count / 2);
+        group.bench_with_input(BenchmarkId::new("trie_params", count), &path, |b, path| {
+            b.iter(|| trie.find(black_box(&HttpMethod::GET), black_box(path)))
+        });
+
+        let mut trie = TrieRouter::new();
+        fill(&mut trie, count, false);
+        let mut hash = HashRouter::new();
+        fill(&mut hash, count, false);
+        let path = format!("/api/resource{}/items", count / 2);
+        group.bench_with_input(BenchmarkId::new("trie_static", count), &path, |b, path| {
+            b.iter(|| trie.find(black_box(&HttpMethod::GET), black_box(path)))
+        });
+        group.bench_with_input(BenchmarkId::new("hash_static", count), &path, |b, path| {
+            b.iter(|| hash.find(black_box(&HttpMethod::GET), black_box(path)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, lookup);
+criterion_main!(benches);
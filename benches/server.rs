@@ -0,0 +1,119 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpStream,
+};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use guest_server::{Server, ServerHandle};
+
+/// Starts a server with a constant route, a parameter route and an echo route.
+fn start() -> ServerHandle {
+    let mut server = Server::new();
+    server.get("/", |_| "ok");
+    server.get("/users/:id", |request| {
+        format!("user {}", request.params["id"])
+    });
+    server.post("/echo", |request| {
+        String::from_utf8_lossy(&request.body).into_owned()
+    });
+    server.bind("127.0.0.1:0").unwrap()
+}
+
+/// A kept-alive connection to the server that sends one request at a time.
+struct Client {
+    reader: BufReader<TcpStream>,
+    stream: TcpStream,
+}
+
+impl Client {
+    fn connect(handle: &ServerHandle) -> Self {
+        let stream = TcpStream::connect(handle.local_addrs()[0]).unwrap();
+        stream.set_nodelay(true).unwrap();
+        Client {
+            reader: BufReader::new(stream.try_clone().unwrap()),
+            stream,
+        }
+    }
+
+    /// Sends a request and reads the whole response, returning its body length.
+    fn send(&mut self, request: &[u8]) -> usize {
+        self.stream.write_all(request).unwrap();
+        let mut length = 0;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.reader.read_line(&mut line).unwrap() == 0 {
+                panic!("connection closed before the end of the response head");
+            }
+            if line == "\r\n" {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+        }
+        let mut body = vec![0; length];
+        self.reader.read_exact(&mut body).unwrap();
+        length
+    }
+}
+
+/// Builds a GET request for `/` carrying `count` headers besides `Host`.
+fn request_with_headers(count: usize) -> Vec<u8> {
+    let mut request = String::from("GET / HTTP/1.1\r\nHost: bench\r\n");
+    for i in 0..count {
+        request.push_str(&format!("X-Header-{}: value-{}\r\n", i, i));
+    }
+    request.push_str("\r\n");
+    request.into_bytes()
+}
+
+fn headers_round_trip(c: &mut Criterion) {
+    let handle = start();
+    let mut client = Client::connect(&handle);
+    let mut group = c.benchmark_group("headers_round_trip");
+    for count in [0, 10, 50] {
+        let request = request_with_headers(count);
+        group.throughput(Throughput::Bytes(request.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::new("headers", count),
+            &request,
+            |b, request| b.iter(|| client.send(request)),
+        );
+    }
+    group.finish();
+    handle.shutdown();
+    handle.join();
+}
+
+fn round_trip(c: &mut Criterion) {
+    let handle = start();
+    let mut client = Client::connect(&handle);
+    let mut group = c.benchmark_group("round_trip");
+    group.bench_function("static", |b| {
+        b.iter(|| client.send(b"GET / HTTP/1.1\r\nHost: bench\r\n\r\n"))
+    });
+    group.bench_function("params", |b| {
+        b.iter(|| client.send(b"GET /users/42 HTTP/1.1\r\nHost: bench\r\n\r\n"))
+    });
+    for size in [1_024, 65_536] {
+        let mut request = format!(
+            "POST /echo HTTP/1.1\r\nHost: bench\r\nContent-Length: {}\r\n\r\n",
+            size
+        )
+        .into_bytes();
+        request.resize(request.len() + size, b'x');
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::new("echo", size), &request, |b, request| {
+            b.iter(|| client.send(request))
+        });
+    }
+    group.finish();
+    handle.shutdown();
+    handle.join();
+}
+
+criterion_group!(benches, headers_round_trip, round_trip);
+criterion_main!(benches);
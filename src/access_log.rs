@@ -0,0 +1,117 @@
+use std::{
+    io::Write,
+    net::IpAddr,
+    sync::{Arc, Mutex, PoisonError},
+    time::SystemTime,
+};
+
+use crate::{
+    date::{Civil, MONTHS},
+    Request,
+};
+
+/// The line format of the access log enabled with `Server::access_log`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Apache Common Log Format:
+    /// `127.0.0.1 - - [10/Oct/2000:13:55:36 +0000] "GET / HTTP/1.1" 200 2326`
+    Common,
+    /// Apache Combined Log Format: the Common format followed by the quoted `Referer` and
+    /// `User-Agent` request headers.
+    Combined,
+}
+
+/// Where and how access log lines are written.
+#[derive(Clone)]
+pub(crate) struct AccessLog {
+    pub(crate) format: LogFormat,
+    pub(crate) writer: Arc<Mutex<dyn Write + Send>>,
+}
+
+impl AccessLog {
+    /// Writes the log line of one served request.
+    ///
+    /// # Parameters
+    /// - 'client' : The IP address of the client, if known.
+    /// - 'request' : The request, or `None` if it could not be parsed.
+    /// - 'status_code' : The status code of the response.
+    /// - 'size' : The length of the response body in bytes.
+    pub(crate) fn write(
+        &self,
+        client: Option<IpAddr>,
+        request: Option<&Request>,
+        status_code: u16,
+        size: usize,
+    ) {
+        let client = client.map_or_else(|| "-".to_string(), |ip| ip.to_string());
+        let request_line = request.map_or_else(
+            || "-".to_string(),
+            |request| {
+                let mut target = request.path.clone();
+                if !request.query.as_str().is_empty() {
+                    target.push('?');
+                    target.push_str(request.query.as_str());
+                }
+                format!(
+                    "{} {} {}",
+                    request.method,
+                    escape(&target),
+                    escape(&request.version)
+                )
+            },
+        );
+        let size = match size {
+            0 => "-".to_string(),
+            size => size.to_string(),
+        };
+        let mut line = format!(
+            "{} - - [{}] \"{}\" {} {}",
+            client,
+            timestamp(SystemTime::now()),
+            request_line,
+            status_code,
+            size
+        );
+        if self.format == LogFormat::Combined {
+            let header = |name| {
+                request
+                    .and_then(|request| request.headers.get(name))
+                    .map_or_else(|| "-".to_string(), |value| escape(value))
+            };
+            line.push_str(&format!(
+                " \"{}\" \"{}\"",
+                header("referer"),
+                header("user-agent")
+            ));
+        }
+        line.push('\n');
+
+        if let Err(e) = self
+            .writer
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .write_all(line.as_bytes())
+        {
+            log_error!("Failed to write access log: {}", e);
+        }
+    }
+}
+
+/// Escapes quotes, backslashes and control characters so a value cannot break the log line.
+fn escape(value: &str) -> String {
+    value.escape_default().to_string()
+}
+
+/// Formats a time as in the Common Log Format, e.g. `10/Oct/2000:13:55:36 +0000`, in UTC.
+fn timestamp(time: SystemTime) -> String {
+    let civil = Civil::from(time);
+    format!(
+        "{:02}/{}/{}:{:02}:{:02}:{:02} +0000",
+        civil.day,
+        MONTHS[civil.month as usize - 1],
+        civil.year,
+        civil.hour,
+        civil.minute,
+        civil.second
+    )
+}
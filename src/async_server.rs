@@ -0,0 +1,397 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    io::{Error, ErrorKind, Read},
+    net::SocketAddr,
+    pin::Pin,
+    sync::{Arc, PoisonError, RwLock},
+    time::Duration,
+};
+
+use socket2::SockRef;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    time::{self, Instant},
+};
+
+use crate::{
+    Config, HttpMethod, HttpResponse, ParseError, PartialRequest, Request, Server, HTTP2_REFUSAL,
+};
+
+type AsyncHandler = Arc<
+    dyn Fn(Request) -> Pin<Box<dyn Future<Output = HttpResponse> + Send>> + Send + Sync + 'static,
+>;
+
+type AsyncRoutes = Arc<RwLock<HashMap<(HttpMethod, String), AsyncHandler>>>;
+
+/// Represents an HTTP server running on the tokio runtime.
+///
+/// This is the asynchronous counterpart of `Server`: connections are `tokio::net::TcpStream`s
+/// served by tasks instead of threads, and handlers are async functions that receive the
+/// `Request` by value. Requests are parsed and responses generated exactly as in `Server`.
+///
+/// Requires the `tokio` feature.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use guest_server::{AsyncServer,HttpResponse,Request};
+///
+/// async fn home(request: Request) -> HttpResponse {
+///     HttpResponse::new(200, Some("Hello, World!".to_string()))
+/// }
+///
+/// async fn run() {
+///     let mut server = AsyncServer::new();
+///     server.get("/", home);
+///     server.listener(8080).await;
+/// }
+/// ```
+pub struct AsyncServer {
+    routes: AsyncRoutes, // A map storing routes and their associated async handlers.
+    config: Config,
+}
+
+impl Default for AsyncServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AsyncServer {
+    /// Creates and initializes a new async server instance.
+    ///
+    /// # Returns
+    /// A new instance of `AsyncServer` with an empty route configuration.
+    pub fn new() -> Self {
+        Self {
+            routes: Arc::new(RwLock::new(HashMap::new())),
+            config: Config::default(),
+        }
+    }
+
+    /// Adds or updates a header sent with every response. See `Server::default_header`.
+    ///
+    /// # Parameters
+    /// - 'key' : The header key.
+    /// - 'value' : The header value.
+    pub fn default_header(&mut self, key: &str, value: &str) {
+        self.config
+            .default_headers
+            .insert(key.to_string(), value.to_string());
+    }
+
+    /// Sets how long a connection may wait for the client to send more of its request. See
+    /// `Server::read_timeout`.
+    ///
+    /// # Parameters
+    /// - 'timeout' : The longest time a single read may wait.
+    pub fn read_timeout(&mut self, timeout: Duration) {
+        self.config.read_timeout = Some(timeout);
+    }
+
+    /// Sets how long a client may take to send a whole request. See
+    /// `Server::request_deadline`.
+    ///
+    /// # Parameters
+    /// - 'deadline' : The longest time from the first byte of a request to its last.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use guest_server::{AsyncServer, HttpResponse, Request};
+    /// use std::io::{Read, Write};
+    /// use std::net::{TcpListener, TcpStream};
+    /// use std::{thread, time::Duration};
+    ///
+    /// async fn upload(request: Request) -> HttpResponse {
+    ///     HttpResponse::from_string(200, format!("{} bytes", request.body.len()))
+    /// }
+    ///
+    /// let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    /// let mut server = AsyncServer::new();
+    /// server.post("/upload", upload);
+    /// server.request_deadline(Duration::from_millis(300));
+    /// thread::spawn(move || {
+    ///     let runtime = tokio::runtime::Builder::new_current_thread()
+    ///         .enable_all()
+    ///         .build()
+    ///         .unwrap();
+    ///     runtime.block_on(server.listener(port));
+    /// });
+    ///
+    /// let connect = || loop {
+    ///     match TcpStream::connect(("127.0.0.1", port)) {
+    ///         Ok(stream) => break stream,
+    ///         Err(_) => thread::sleep(Duration::from_millis(10)),
+    ///     }
+    /// };
+    /// let mut stream = connect();
+    /// let head = "POST /upload HTTP/1.1\r\nHost: test\r\nContent-Length: 20000\r\n\r\n";
+    /// stream.write_all(head.as_bytes()).unwrap();
+    /// for _ in 0..5 {
+    ///     thread::sleep(Duration::from_millis(5));
+    ///     stream.write_all(&[b'x'; 4000]).unwrap();
+    /// }
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response).unwrap();
+    /// assert!(response.ends_with("\r\n\r\n20000 bytes"));
+    ///
+    /// // A client that never finishes its request
+    /// let mut stream = connect();
+    /// let request = "POST /upload HTTP/1.1\r\nHost: test\r\nContent-Length: 10\r\n\r\nabc";
+    /// stream.write_all(request.as_bytes()).unwrap();
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response).unwrap();
+    /// assert!(response.starts_with("HTTP/1.1 408 Request Timeout\r\n"));
+    /// ```
+    pub fn request_deadline(&mut self, deadline: Duration) {
+        self.config.request_deadline = Some(deadline);
+    }
+
+    /// Sets the largest request body accepted. See `Server::max_body`.
+    ///
+    /// # Parameters
+    /// - 'length' : The maximum body length in bytes.
+    pub fn max_body(&mut self, length: usize) {
+        self.config.max_body = length;
+    }
+
+    /// Registers a route with a specific HTTP method, path, and async handler.
+    ///
+    /// # Parameters
+    /// - 'method' : The HTTP method (GET, POST, PATCH) for this route.
+    /// - 'path' : The route path (e.g., '/home').
+    /// - 'handler' : The async function that processes the request for this path.
+    fn route<F, Fut>(&mut self, method: HttpMethod, path: &str, handler: F)
+    where
+        F: Fn(Request) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = HttpResponse> + Send + 'static,
+    {
+        let handler: AsyncHandler = Arc::new(move |request| Box::pin(handler(request)));
+        self.routes
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert((method, path.to_string()), handler);
+    }
+
+    /// Registers a GET route with a specified path and async handler.
+    ///
+    /// # Parameters
+    /// - 'path' : The route path to register, e.g., '/home'.
+    /// - 'handler' : The async function that processes the request for this path.
+    pub fn get<F, Fut>(&mut self, path: &str, handler: F)
+    where
+        F: Fn(Request) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = HttpResponse> + Send + 'static,
+    {
+        self.route(HttpMethod::GET, path, handler);
+    }
+
+    /// Registers a POST route with a specified path and async handler.
+    ///
+    /// # Parameters
+    /// - 'path' : The route path to register, e.g., '/submit'.
+    /// - 'handler' : The async function that processes the request for this path.
+    pub fn post<F, Fut>(&mut self, path: &str, handler: F)
+    where
+        F: Fn(Request) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = HttpResponse> + Send + 'static,
+    {
+        self.route(HttpMethod::POST, path, handler);
+    }
+
+    /// Registers a PATCH route with a specified path and async handler.
+    ///
+    /// # Parameters
+    /// - 'path' : The route path to register, e.g., '/users/1'.
+    /// - 'handler' : The async function that processes the request for this path.
+    pub fn patch<F, Fut>(&mut self, path: &str, handler: F)
+    where
+        F: Fn(Request) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = HttpResponse> + Send + 'static,
+    {
+        self.route(HttpMethod::PATCH, path, handler);
+    }
+
+    /// Starts the server and listens for incoming connections on the specified port.
+    ///
+    /// Each connection is served by its own tokio task; this future only completes if the
+    /// listening socket fails.
+    ///
+    /// # Parameters
+    /// - 'port' : The port number to listen on.
+    pub async fn listener(&self, port: u16) {
+        let addr: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+        let listener = TcpListener::bind(addr).await.unwrap();
+        let config = Arc::new(self.config.clone());
+        // Listen for incoming connections
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let routes = Arc::clone(&self.routes);
+                    let config = Arc::clone(&config);
+                    tokio::spawn(async move {
+                        if let Err(e) = AsyncServer::handle_connection(routes, config, stream).await
+                        {
+                            // A client going away mid-response is routine, not a failure
+                            if Server::is_timeout(&e) {
+                                log_debug!("Connection timed out: {}", e);
+                            } else if !Server::is_disconnect(&e) {
+                                log_error!("Connection failed: {}", e);
+                            }
+                        }
+                    });
+                }
+                Err(e) => log_error!("Failed to accept connection: {}", e),
+            }
+        }
+    }
+
+    /// Handles the incoming TCP connection, processes the HTTP request, and sends back a response.
+    ///
+    /// The request is buffered as it arrives, up to the length its head announces, then
+    /// dispatched to the async handler for its method and path. A request not complete within
+    /// the request deadline is answered with 408, and a read waiting longer than the read
+    /// timeout ends the connection.
+    async fn handle_connection(
+        routes: AsyncRoutes,
+        config: Arc<Config>,
+        mut stream: TcpStream,
+    ) -> Result<(), Error> {
+        Server::set_tcp_keepalive(SockRef::from(&stream), &config)?;
+        let deadline = config
+            .request_deadline
+            .map(|deadline| Instant::now() + deadline);
+        let mut partial = PartialRequest::default();
+        let mut received = Vec::new();
+        let mut buffer = [0; 4096];
+        let request = loop {
+            let wanted = partial.wanted(&config);
+            if received.len() < wanted {
+                let room = buffer.len().min(wanted - received.len());
+                let read = stream.read(&mut buffer[..room]);
+                // No single read may outlast the deadline
+                let timeout = match (deadline, config.read_timeout) {
+                    (Some(deadline), timeout) => {
+                        let remaining = deadline.saturating_duration_since(Instant::now());
+                        Some(timeout.map_or(remaining, |timeout| timeout.min(remaining)))
+                    }
+                    (None, timeout) => timeout,
+                };
+                let read = match timeout {
+                    Some(timeout) => time::timeout(timeout, read).await,
+                    None => Ok(read.await),
+                };
+                match read {
+                    Ok(read) => match read? {
+                        0 => return Err(ErrorKind::UnexpectedEof.into()),
+                        n => received.extend_from_slice(&buffer[..n]),
+                    },
+                    Err(_) if deadline.is_some_and(|deadline| Instant::now() >= deadline) => {
+                        log_debug!("Request not received within its deadline");
+                        break Err(408);
+                    }
+                    Err(_) => return Err(Error::new(ErrorKind::TimedOut, "Read timed out")),
+                }
+            }
+            match partial.parse(&received, &config) {
+                Ok(Some(request)) => break Ok(request),
+                // Not everything has arrived yet; keep reading
+                Ok(None) => {}
+                Err(ParseError::Status(status_code)) => break Err(status_code),
+                Err(ParseError::Http2) => return stream.write_all(&HTTP2_REFUSAL).await,
+                Err(ParseError::Io(e)) => return Err(e),
+            }
+        };
+
+        let (response, is_head, request_id) = match request {
+            Ok(request) => {
+                let is_head = request.method == HttpMethod::HEAD;
+                let request_id = request.request_id.clone();
+                (
+                    AsyncServer::processing_response(&routes, request).await,
+                    is_head,
+                    request_id,
+                )
+            }
+            Err(status_code) => (
+                HttpResponse::new(status_code, None),
+                false,
+                Server::generate_request_id(),
+            ),
+        };
+
+        // Each connection serves a single request, so a streamed body ends with the connection
+        let mut response = response.close_connection();
+        let res = Server::generate_http_response(&response, &config, is_head, &request_id);
+        stream.write_all(&res).await?;
+        match response.stream.take().filter(|_| !is_head) {
+            Some(body) => AsyncServer::write_body(&mut stream, body).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Copies a streamed response body to the client. The body is a blocking reader, so each
+    /// read runs on tokio's blocking thread pool rather than stalling the runtime.
+    async fn write_body(
+        stream: &mut TcpStream,
+        mut body: Box<dyn Read + Send>,
+    ) -> Result<(), Error> {
+        loop {
+            let (returned, chunk) = tokio::task::spawn_blocking(move || {
+                let mut buffer = vec![0; 8192];
+                let result = loop {
+                    match body.read(&mut buffer) {
+                        Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                        result => break result,
+                    }
+                };
+                (body, result.map(|n| buffer[..n].to_vec()))
+            })
+            .await
+            .map_err(Error::other)?;
+            body = returned;
+            let chunk = chunk?;
+            if chunk.is_empty() {
+                return Ok(());
+            }
+            stream.write_all(&chunk).await?;
+        }
+    }
+
+    /// Invokes the async handler registered for the request's method and path.
+    ///
+    /// # Returns
+    /// The handler's response, 500 if it panicked, or a 404 response if no handler is found.
+    /// HEAD requests are served by the GET route for the same path. TRACE and CONNECT
+    /// requests are refused with 405.
+    async fn processing_response(routes: &AsyncRoutes, mut request: Request) -> HttpResponse {
+        let method = match request.method {
+            HttpMethod::HEAD => HttpMethod::GET,
+            HttpMethod::TRACE | HttpMethod::CONNECT => return HttpResponse::status(405),
+            ref method => method.clone(),
+        };
+        let handler = routes
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&(method, request.path.clone()))
+            .cloned();
+        match handler {
+            Some(handler) => {
+                request.route = Some(request.path.clone()); // Paths are matched exactly.
+
+                // On a task of its own, a panicking handler fails the task, not the connection
+                match tokio::spawn(async move { handler(request).await }).await {
+                    Ok(response) => response,
+                    Err(e) => {
+                        log_error!("Handler failed: {}", e);
+                        HttpResponse::status(500)
+                    }
+                }
+            }
+            None => HttpResponse::status(404),
+        }
+    }
+}
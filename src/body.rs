@@ -0,0 +1,105 @@
+use std::{error, fmt};
+
+use crate::{response::IntoResponse, HttpResponse, Request};
+
+/// Why a request body could not be read as text, as returned by `Request::body_as_string`.
+///
+/// Converts into the response to send: 400 Bad Request for a body that is not validly
+/// encoded, and 415 Unsupported Media Type for a charset the server does not decode.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BodyError {
+    /// The body is not valid in its charset, e.g. broken UTF-8.
+    InvalidEncoding,
+    /// The `Content-Type` names a charset that cannot be decoded, as given.
+    UnsupportedCharset(String),
+}
+
+impl fmt::Display for BodyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BodyError::InvalidEncoding => f.write_str("request body is not validly encoded"),
+            BodyError::UnsupportedCharset(charset) => {
+                write!(f, "unsupported request body charset {:?}", charset)
+            }
+        }
+    }
+}
+
+impl error::Error for BodyError {}
+
+impl IntoResponse for BodyError {
+    fn into_response(self) -> HttpResponse {
+        match self {
+            BodyError::InvalidEncoding => HttpResponse::status(400),
+            BodyError::UnsupportedCharset(_) => HttpResponse::status(415),
+        }
+    }
+}
+
+impl Request {
+    /// Returns the request body as text, decoded according to the `charset` of its
+    /// `Content-Type`.
+    ///
+    /// UTF-8 is assumed without a charset. `us-ascii` and `iso-8859-1` (also as `latin1`)
+    /// are decoded as well; other charsets are refused rather than guessed at. A UTF-8 byte
+    /// order mark at the start is dropped.
+    ///
+    /// # Returns
+    /// The text, or the error, which converts into a 400 or 415 response, so a handler
+    /// returning a `Result` can pass it straight on.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "testing")] {
+    /// use guest_server::{BodyError, Server, TestClient};
+    ///
+    /// let mut server = Server::new();
+    /// server.post("/shout", |request| -> Result<String, BodyError> {
+    ///     Ok(request.body_as_string()?.to_uppercase())
+    /// });
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let client = TestClient::new(handle.local_addrs()[0]);
+    /// assert_eq!(client.post("/shout", "grüß".as_bytes()).unwrap().text(), "GRÜSS");
+    /// assert_eq!(client.post("/shout", b"\xff").unwrap().status_code, 400);
+    /// let latin1 = [("Content-Type", "text/plain; charset=ISO-8859-1")];
+    /// let response = client.request("POST", "/shout", &latin1, b"gr\xfc\xdf").unwrap();
+    /// assert_eq!(response.text(), "GRÜSS");
+    /// let koi8 = [("Content-Type", "text/plain; charset=koi8-r")];
+    /// let response = client.request("POST", "/shout", &koi8, b"text").unwrap();
+    /// assert_eq!(response.status_code, 415);
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// # }
+    /// ```
+    pub fn body_as_string(&self) -> Result<String, BodyError> {
+        let charset = self.header("content-type").and_then(|content_type| {
+            content_type.split(';').skip(1).find_map(|parameter| {
+                let (name, value) = parameter.split_once('=')?;
+                name.trim()
+                    .eq_ignore_ascii_case("charset")
+                    .then(|| value.trim().trim_matches('"').to_ascii_lowercase())
+            })
+        });
+        match charset.as_deref() {
+            None | Some("utf-8" | "utf8") => {
+                let body = self
+                    .body
+                    .strip_prefix(b"\xef\xbb\xbf")
+                    .unwrap_or(&self.body);
+                String::from_utf8(body.to_vec()).map_err(|_| BodyError::InvalidEncoding)
+            }
+            Some("us-ascii" | "ascii") if self.body.is_ascii() => {
+                Ok(self.body.iter().map(|&byte| byte as char).collect())
+            }
+            Some("us-ascii" | "ascii") => Err(BodyError::InvalidEncoding),
+            // Latin-1 bytes are the first 256 Unicode code points
+            Some("iso-8859-1" | "latin1") => {
+                Ok(self.body.iter().map(|&byte| byte as char).collect())
+            }
+            Some(charset) => Err(BodyError::UnsupportedCharset(charset.to_string())),
+        }
+    }
+}
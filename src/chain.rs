@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use crate::{response::IntoResponse, Handler, HttpResponse, Request};
+
+/// Handlers tried in turn for one route, e.g. a feature-flagged or experimental handler in
+/// front of the regular one.
+///
+/// Each handler either answers the request or passes it on by returning 404 Not Found, which
+/// is the signal to try the next one. The first response with any other status is sent. If
+/// every handler passes, the last one's 404 is sent as it is, body included; the server's
+/// `fallback` is not consulted, as the route did match.
+///
+/// Register a chain like any handler, with `Chain::handler`.
+///
+/// # Example
+///
+/// ```rust
+/// # #[cfg(feature = "testing")] {
+/// use guest_server::{Chain, HttpResponse, Server, TestClient};
+///
+/// let mut server = Server::new();
+/// let checkout = Chain::new(|request| match request.header("X-Beta") {
+///     Some(_) => HttpResponse::from_static(200, "new checkout"),
+///     None => HttpResponse::status(404),
+/// })
+/// .or(|_| "checkout");
+/// server.get("/checkout", checkout.handler());
+/// let handle = server.bind("127.0.0.1:0").unwrap();
+///
+/// let client = TestClient::new(handle.local_addrs()[0]);
+/// assert_eq!(client.get("/checkout").unwrap().text(), "checkout");
+/// let beta = client.request("GET", "/checkout", &[("X-Beta", "1")], b"");
+/// assert_eq!(beta.unwrap().text(), "new checkout");
+///
+/// handle.shutdown();
+/// handle.join();
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Chain {
+    handlers: Vec<Handler>,
+}
+
+impl Chain {
+    /// Creates a chain starting with a handler.
+    ///
+    /// # Parameters
+    /// - 'handler' : The handler tried first.
+    pub fn new<F, T>(handler: F) -> Self
+    where
+        F: Fn(&Request) -> T + Send + Sync + 'static,
+        T: IntoResponse,
+    {
+        Self {
+            handlers: vec![Chain::erase(handler)],
+        }
+    }
+
+    /// Adds a handler, tried when every handler before it passed with 404.
+    ///
+    /// # Parameters
+    /// - 'handler' : The handler to try next.
+    pub fn or<F, T>(mut self, handler: F) -> Self
+    where
+        F: Fn(&Request) -> T + Send + Sync + 'static,
+        T: IntoResponse,
+    {
+        self.handlers.push(Chain::erase(handler));
+        self
+    }
+
+    /// Turns the chain into a single handler, to register with `Server::get` and the like.
+    pub fn handler(self) -> impl Fn(&Request) -> HttpResponse + Send + Sync + 'static {
+        move |request| {
+            let mut response = HttpResponse::status(404);
+            for handler in &self.handlers {
+                response = handler(request);
+                if response.status_code != 404 {
+                    break;
+                }
+            }
+            response
+        }
+    }
+
+    /// Converts a handler to the type every link of the chain shares.
+    fn erase<F, T>(handler: F) -> Handler
+    where
+        F: Fn(&Request) -> T + Send + Sync + 'static,
+        T: IntoResponse,
+    {
+        Arc::new(move |request| handler(request).into_response())
+    }
+}
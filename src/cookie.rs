@@ -0,0 +1,292 @@
+use std::{collections::HashMap, fmt};
+
+use crate::Server;
+
+/// The `SameSite` attribute of a cookie.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+/// A cookie to set on the client, rendered as a `Set-Cookie` header.
+///
+/// # Example
+///
+/// ```rust
+/// use guest_server::{Cookie, HttpResponse, SameSite};
+///
+/// let response = HttpResponse::new(200, None).set_cookie(
+///     Cookie::new("theme", "dark")
+///         .path("/")
+///         .max_age(3600)
+///         .http_only(true)
+///         .same_site(SameSite::Lax),
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    path: Option<String>,
+    domain: Option<String>,
+    max_age: Option<u64>,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>,
+    signed: bool,
+}
+
+impl Cookie {
+    /// Creates a session cookie with the given name and value and no attributes.
+    ///
+    /// The name must be a token, like a header name, and the value may only hold printable
+    /// ASCII other than space, `"`, `,`, `;` and `\`, optionally within double quotes. A
+    /// cookie that breaks these rules, or whose `Path` or `Domain` holds a `;` or control
+    /// character, is left out of the response with an error logged, rather than let its
+    /// value add attributes of its own. Encode values that may hold other characters, such as
+    /// user input, e.g. by percent-encoding them.
+    ///
+    /// # Parameters
+    /// - 'name' : The cookie name.
+    /// - 'value' : The cookie value.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "testing")] {
+    /// use guest_server::{Cookie, HttpResponse, Server, TestClient};
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/theme", |request| {
+    ///     let theme = request.query.get("set").unwrap_or_default();
+    ///     HttpResponse::status(204).set_cookie(Cookie::new("theme", &theme).path("/"))
+    /// });
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let client = TestClient::new(handle.local_addrs()[0]);
+    /// let response = client.get("/theme?set=dark").unwrap();
+    /// assert_eq!(response.headers["set-cookie"], "theme=dark; Path=/");
+    /// let response = client.get("/theme?set=dark%3B%20Domain%3Devil.example").unwrap();
+    /// assert!(!response.headers.contains_key("set-cookie"));
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// # }
+    /// ```
+    pub fn new(name: &str, value: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            value: value.to_string(),
+            path: None,
+            domain: None,
+            max_age: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+            signed: false,
+        }
+    }
+
+    /// Sets the `Path` attribute.
+    pub fn path(mut self, path: &str) -> Self {
+        self.path = Some(path.to_string());
+        self
+    }
+
+    /// Sets the `Domain` attribute.
+    pub fn domain(mut self, domain: &str) -> Self {
+        self.domain = Some(domain.to_string());
+        self
+    }
+
+    /// Sets the `Max-Age` attribute, in seconds. A max age of 0 deletes the cookie.
+    pub fn max_age(mut self, seconds: u64) -> Self {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    /// Sets the `Secure` attribute, restricting the cookie to HTTPS.
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Sets the `HttpOnly` attribute, hiding the cookie from scripts.
+    pub fn http_only(mut self, http_only: bool) -> Self {
+        self.http_only = http_only;
+        self
+    }
+
+    /// Sets the `SameSite` attribute.
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = Some(same_site);
+        self
+    }
+
+    /// Marks the cookie to be signed with the server's cookie secret when it is sent, so that
+    /// a value altered by the client is rejected when read back through
+    /// `Request::signed_cookies`.
+    ///
+    /// Requires the `signed-cookies` feature and a secret set with `Server::cookie_secret`.
+    #[cfg(feature = "signed-cookies")]
+    pub fn signed(mut self) -> Self {
+        self.signed = true;
+        self
+    }
+
+    /// Renders the `Set-Cookie` header value, signing the value first if the cookie is signed.
+    ///
+    /// # Returns
+    /// The header value, or why the cookie cannot be sent: a name, value or attribute that
+    /// would not stay in its place in the header, or a signed cookie without a secret.
+    pub(crate) fn to_header(&self, secret: Option<&[u8]>) -> Result<String, &'static str> {
+        if !Server::is_token(&self.name) {
+            return Err("its name is not a token");
+        }
+        if !is_cookie_value(&self.value) {
+            return Err("its value holds characters a cookie cannot");
+        }
+        let is_attribute = |value: &String| !value.contains(|c: char| c == ';' || c.is_control());
+        if !self.path.iter().chain(&self.domain).all(is_attribute) {
+            return Err("an attribute holds a `;` or control character");
+        }
+        if !self.signed {
+            return Ok(self.to_string());
+        }
+        let signature = secret
+            .and_then(|secret| sign(secret, &self.name, &self.value))
+            .ok_or("it is signed, but no cookie secret is configured")?;
+        let signed = Cookie {
+            value: format!("{}.{}", self.value, signature),
+            signed: false,
+            ..self.clone()
+        };
+        Ok(signed.to_string())
+    }
+}
+
+impl fmt::Display for Cookie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.name, self.value)?;
+        if let Some(path) = &self.path {
+            write!(f, "; Path={}", path)?;
+        }
+        if let Some(domain) = &self.domain {
+            write!(f, "; Domain={}", domain)?;
+        }
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age)?;
+        }
+        if self.secure {
+            write!(f, "; Secure")?;
+        }
+        if self.http_only {
+            write!(f, "; HttpOnly")?;
+        }
+        match self.same_site {
+            Some(SameSite::Strict) => write!(f, "; SameSite=Strict")?,
+            Some(SameSite::Lax) => write!(f, "; SameSite=Lax")?,
+            Some(SameSite::None) => write!(f, "; SameSite=None")?,
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+/// Reports whether a cookie value is `cookie-octet`s, optionally within double quotes, as
+/// RFC 6265 allows: printable ASCII other than space, `"`, `,`, `;` and `\`.
+fn is_cookie_value(value: &str) -> bool {
+    let value = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value);
+    value
+        .bytes()
+        .all(|byte| byte.is_ascii_graphic() && !matches!(byte, b'"' | b',' | b';' | b'\\'))
+}
+
+/// Parses the value of a `Cookie` request header, e.g. `theme=dark; sid=abc`.
+///
+/// # Returns
+/// A map of cookie names to values, with surrounding double quotes removed.
+pub(crate) fn parse_cookies(header: &str) -> HashMap<String, String> {
+    header
+        .split(';')
+        .filter_map(|pair| {
+            let (name, value) = pair.trim().split_once('=')?;
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(value);
+            Some((name.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Computes the hex-encoded HMAC-SHA256 of a cookie's name and value.
+#[cfg(feature = "signed-cookies")]
+fn sign(secret: &[u8], name: &str, value: &str) -> Option<String> {
+    use hmac::Mac;
+
+    let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(secret).ok()?;
+    mac.update(name.as_bytes());
+    mac.update(b"=");
+    mac.update(value.as_bytes());
+    Some(
+        mac.finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect(),
+    )
+}
+
+#[cfg(not(feature = "signed-cookies"))]
+fn sign(_secret: &[u8], _name: &str, _value: &str) -> Option<String> {
+    None
+}
+
+/// Checks the signatures of signed cookies, comparing in constant time.
+///
+/// # Parameters
+/// - 'secret' : The server's cookie secret.
+/// - 'cookies' : All cookies sent with the request.
+///
+/// # Returns
+/// The original values of the cookies whose signature is valid; tampered or unsigned cookies
+/// are left out.
+#[cfg(feature = "signed-cookies")]
+pub(crate) fn verify_cookies(
+    secret: &[u8],
+    cookies: &HashMap<String, String>,
+) -> HashMap<String, String> {
+    use hmac::Mac;
+
+    cookies
+        .iter()
+        .filter_map(|(name, signed_value)| {
+            let (value, signature) = signed_value.rsplit_once('.')?;
+            let signature = decode_hex(signature)?;
+            let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(secret).ok()?;
+            mac.update(name.as_bytes());
+            mac.update(b"=");
+            mac.update(value.as_bytes());
+            mac.verify_slice(&signature).ok()?;
+            Some((name.clone(), value.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(feature = "signed-cookies")]
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
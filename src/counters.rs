@@ -0,0 +1,93 @@
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc,
+};
+
+/// Running totals of the connections and requests a `Server` has handled, always kept.
+///
+/// Obtained from `Server::counters` or `ServerHandle::counters`, and shared by every listener
+/// of the server. Each count is a single atomic, so keeping them costs requests next to
+/// nothing; read them in tests, or expose them to monitoring. For per-route counts and
+/// latencies, see `Metrics`.
+///
+/// # Example
+///
+/// ```rust
+/// # #[cfg(feature = "testing")] {
+/// use guest_server::{Server, TestClient};
+///
+/// let mut server = Server::new();
+/// server.get("/", |_| "ok");
+/// let handle = server.bind("127.0.0.1:0").unwrap();
+///
+/// let client = TestClient::new(handle.local_addrs()[0]);
+/// for path in ["/", "/", "/missing"] {
+///     client.get(path).unwrap();
+/// }
+///
+/// let counters = handle.counters();
+/// assert_eq!(counters.connections(), 3);
+/// assert_eq!(counters.requests(), 3);
+/// assert_eq!(counters.responses(2), 2);
+/// assert_eq!(counters.responses(4), 1);
+/// assert_eq!(counters.in_flight(), 0);
+///
+/// handle.shutdown();
+/// handle.join();
+/// // Still readable once the server has stopped
+/// assert_eq!(counters.requests(), 3);
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct Counters {
+    connections: AtomicU64,
+    requests: AtomicU64,
+    responses: [AtomicU64; 5],              // By status class, 1xx to 5xx.
+    pub(crate) in_flight: Arc<AtomicUsize>, // Kept by an `ActiveConnection` per request.
+}
+
+impl Counters {
+    /// Returns the number of connections accepted, including those turned away with 503 by
+    /// `max_connections` or `overload_policy`.
+    pub fn connections(&self) -> u64 {
+        self.connections.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of requests answered, including those answered with an error
+    /// because they could not be parsed.
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of responses sent with a status code of a class.
+    ///
+    /// # Parameters
+    /// - 'class' : The first digit of the status codes, e.g. `5` for 5xx.
+    ///
+    /// # Returns
+    /// The count, or 0 for a class other than 1 to 5.
+    pub fn responses(&self, class: u16) -> u64 {
+        match class {
+            1..=5 => self.responses[class as usize - 1].load(Ordering::Relaxed),
+            _ => 0,
+        }
+    }
+
+    /// Returns the number of requests being served right now.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Counts an accepted connection.
+    pub(crate) fn connection(&self) {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts an answered request by the status code of its response.
+    pub(crate) fn response(&self, status_code: u16) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        if let 100..=599 = status_code {
+            self.responses[status_code as usize / 100 - 1].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
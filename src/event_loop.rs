@@ -0,0 +1,272 @@
+use std::{
+    collections::HashMap,
+    io::{ErrorKind, Read, Result, Write},
+    net::SocketAddr,
+    sync::Arc,
+};
+
+use mio::{
+    event::Event,
+    net::{TcpListener, TcpStream},
+    Events, Interest, Poll, Registry, Token,
+};
+
+use socket2::SockRef;
+
+use crate::{
+    ActiveConnection, Config, ParseError, PartialRequest, Router, Routes, Server, HTTP2_REFUSAL,
+};
+
+const LISTENER: Token = Token(0);
+
+impl<R: Router> Server<R> {
+    /// Starts the server on a single-threaded event loop and listens on the specified port.
+    ///
+    /// Unlike `listener`, which spawns a thread per connection, every connection is multiplexed
+    /// on one thread with non-blocking sockets (epoll/kqueue through `mio`). Idle connections,
+    /// such as clients waiting on a long poll, cost a buffer instead of a thread. Handlers run
+    /// on the event loop thread itself, so a slow handler delays every other connection.
+    ///
+    /// A request is buffered as it arrives and parsed once its head is complete. The head may
+    /// take up to `max_request_line` plus 64 KiB, beyond which it is refused with 431 Request
+    /// Header Fields Too Large, and a body longer than `max_body` is refused with 413 Payload
+    /// Too Large as soon as its head announces it.
+    ///
+    /// Requires the `event-loop` feature.
+    ///
+    /// # Parameters
+    /// - 'port' : The port number to listen on.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use guest_server::{Server,HttpResponse};
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/", |_| HttpResponse::new(200, Some("Hello, World!".to_string())));
+    /// server.listener_event_loop(8080);
+    /// ```
+    ///
+    /// A request sent in pieces:
+    ///
+    /// ```rust
+    /// use guest_server::Server;
+    /// use std::io::{Read, Write};
+    /// use std::net::{TcpListener, TcpStream};
+    /// use std::{thread, time::Duration};
+    ///
+    /// let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    /// let mut server = Server::new();
+    /// server.post("/upload", |request| format!("{} bytes", request.body.len()));
+    /// server.max_body(64 * 1024);
+    /// thread::spawn(move || server.listener_event_loop(port));
+    ///
+    /// let connect = || loop {
+    ///     match TcpStream::connect(("127.0.0.1", port)) {
+    ///         Ok(stream) => break stream,
+    ///         Err(_) => thread::sleep(Duration::from_millis(10)),
+    ///     }
+    /// };
+    /// let mut stream = connect();
+    /// stream.write_all(b"POST /upload HTTP/1.1\r\nHost: te").unwrap();
+    /// thread::sleep(Duration::from_millis(20));
+    /// stream.write_all(b"st\r\nContent-Length: 40000\r\n\r\n").unwrap();
+    /// for _ in 0..10 {
+    ///     thread::sleep(Duration::from_millis(5));
+    ///     stream.write_all(&[b'x'; 4000]).unwrap();
+    /// }
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response).unwrap();
+    /// assert!(response.ends_with("\r\n\r\n40000 bytes"));
+    ///
+    /// let mut stream = connect();
+    /// let head = "POST /upload HTTP/1.1\r\nHost: test\r\nContent-Length: 99999999\r\n\r\n";
+    /// stream.write_all(head.as_bytes()).unwrap();
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response).unwrap();
+    /// assert!(response.starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+    /// ```
+    pub fn listener_event_loop(&self, port: u16) {
+        let addr: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+        let mut listener = TcpListener::bind(addr).unwrap();
+        let mut poll = Poll::new().unwrap();
+        poll.registry()
+            .register(&mut listener, LISTENER, Interest::READABLE)
+            .unwrap();
+
+        let config = Arc::new(self.config.clone());
+        let mut events = Events::with_capacity(1024);
+        let mut connections: HashMap<Token, Connection> = HashMap::new();
+        let mut next_token = LISTENER.0 + 1;
+
+        loop {
+            if let Err(e) = poll.poll(&mut events, None) {
+                if e.kind() == ErrorKind::Interrupted {
+                    continue;
+                }
+                log_error!("Event loop failed: {}", e);
+                return;
+            }
+
+            for event in events.iter() {
+                if event.token() == LISTENER {
+                    // Accept everything pending; the listener only signals readiness once
+                    loop {
+                        match listener.accept() {
+                            Ok((mut stream, _)) => {
+                                config.counters.connection();
+                                if let Err(e) =
+                                    Server::set_tcp_keepalive(SockRef::from(&stream), &config)
+                                {
+                                    log_error!("Failed to enable TCP keepalive: {}", e);
+                                }
+                                let token = Token(next_token);
+                                next_token += 1;
+                                match poll.registry().register(
+                                    &mut stream,
+                                    token,
+                                    Interest::READABLE,
+                                ) {
+                                    Ok(()) => {
+                                        let active =
+                                            ActiveConnection::new(&config.active_connections);
+                                        connections.insert(token, Connection::new(stream, active));
+                                    }
+                                    Err(e) => log_error!("Failed to register connection: {}", e),
+                                }
+                            }
+                            Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                            Err(e) => {
+                                log_error!("Failed to accept connection: {}", e);
+                                break;
+                            }
+                        }
+                    }
+                    continue;
+                }
+
+                let done = match connections.get_mut(&event.token()) {
+                    Some(connection) => connection
+                        .ready(event, &self.routes, &config, poll.registry())
+                        .unwrap_or_else(|e| {
+                            if !Server::is_disconnect(&e) {
+                                log_error!("Connection failed: {}", e);
+                            }
+                            true
+                        }),
+                    None => false,
+                };
+                if done {
+                    if let Some(mut connection) = connections.remove(&event.token()) {
+                        let _ = poll.registry().deregister(&mut connection.stream);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The state of one connection on the event loop: the request bytes received so far and, once
+/// the request is complete, the response still to be written.
+struct Connection {
+    stream: TcpStream,
+    received: Vec<u8>,
+    request: PartialRequest,
+    response: Option<Vec<u8>>,
+    written: usize,
+    _active: ActiveConnection, // Counts the connection until it is dropped.
+}
+
+impl Connection {
+    fn new(stream: TcpStream, active: ActiveConnection) -> Self {
+        Self {
+            stream,
+            received: Vec::new(),
+            request: PartialRequest::default(),
+            response: None,
+            written: 0,
+            _active: active,
+        }
+    }
+
+    /// Advances the connection after a readiness event.
+    ///
+    /// # Returns
+    /// `true` once the connection is finished and should be dropped.
+    fn ready<R: Router>(
+        &mut self,
+        event: &Event,
+        routes: &Routes<R>,
+        config: &Config,
+        registry: &Registry,
+    ) -> Result<bool> {
+        if event.is_readable() && self.response.is_none() {
+            let request = loop {
+                let wanted = self.request.wanted(config);
+                let open = self.receive(wanted)?;
+                match self.request.parse(&self.received, config) {
+                    Ok(Some(request)) => break Ok(request),
+                    // The head asked for more than was wanted before it; more may be waiting
+                    Ok(None) if self.received.len() >= wanted => {}
+                    // Not everything has arrived yet; wait for the next readiness event
+                    Ok(None) => return Ok(!open),
+                    Err(ParseError::Status(status_code)) => break Err(status_code),
+                    Err(ParseError::Http2) => {
+                        self.response = Some(HTTP2_REFUSAL.to_vec());
+                        registry.reregister(&mut self.stream, event.token(), Interest::WRITABLE)?;
+                        return Ok(false);
+                    }
+                    Err(ParseError::Io(e)) => return Err(e),
+                }
+            };
+
+            let client = self.stream.peer_addr().ok().map(|addr| addr.ip());
+            // Each connection serves a single request, so a streamed body ends with the connection
+            let response = Server::serve(routes, config, request, None, client, false);
+            let mut bytes = response.bytes;
+            if let Some(body) = response.stream {
+                // Reading blocks the event loop; the body is buffered before it is written
+                Server::write_body(&mut bytes, body, false, None)?;
+            }
+            self.response = Some(bytes);
+            registry.reregister(&mut self.stream, event.token(), Interest::WRITABLE)?;
+        }
+
+        match &self.response {
+            Some(response) => {
+                // Write as much as the socket accepts; the rest waits for the next writable event
+                while self.written < response.len() {
+                    match self.stream.write(&response[self.written..]) {
+                        Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                        Ok(n) => self.written += n,
+                        Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(false),
+                        Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Reads what is available on the socket into the receive buffer, until it holds `wanted`
+    /// bytes.
+    ///
+    /// # Returns
+    /// `false` if the client closed its side of the connection.
+    fn receive(&mut self, wanted: usize) -> Result<bool> {
+        let mut buffer = [0; 4096];
+        while self.received.len() < wanted {
+            let room = buffer.len().min(wanted - self.received.len());
+            match self.stream.read(&mut buffer[..room]) {
+                Ok(0) => return Ok(false),
+                Ok(n) => self.received.extend_from_slice(&buffer[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(true),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(true)
+    }
+}
@@ -0,0 +1,78 @@
+use serde_json::Value;
+
+use crate::Request;
+
+/// Applies a JSON Merge Patch (RFC 7386) to a document in place.
+///
+/// Members of a patch object replace the same members of the target, recursively for nested
+/// objects; a `null` member deletes the member from the target. A patch that is not an object
+/// replaces the whole target. Requires the `json` feature.
+///
+/// # Parameters
+/// - 'target' : The document to update, e.g. the stored resource.
+/// - 'patch' : The merge patch, usually the body of a PATCH request.
+///
+/// # Example
+///
+/// ```rust
+/// use guest_server::merge_patch;
+/// use serde_json::json;
+///
+/// let mut user = json!({"name": "Ada", "email": "ada@example.com", "tags": {"admin": true}});
+/// merge_patch(&mut user, &json!({"email": null, "tags": {"editor": true}}));
+/// assert_eq!(user, json!({"name": "Ada", "tags": {"admin": true, "editor": true}}));
+/// ```
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    let Value::Object(target) = target else {
+        unreachable!("target was just made an object");
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(target.entry(key.as_str()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+impl Request {
+    /// Applies the request body, parsed as a JSON Merge Patch, to a document.
+    ///
+    /// Requires the `json` feature.
+    ///
+    /// # Parameters
+    /// - 'target' : The document to update.
+    ///
+    /// # Returns
+    /// The parse error if the body is not valid JSON, so handlers can answer 400; `target` is
+    /// left untouched in that case.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use guest_server::{HttpResponse,Server};
+    /// use serde_json::json;
+    ///
+    /// let mut server = Server::new();
+    /// server.patch("/settings", |request| {
+    ///     let mut settings = json!({"theme": "light", "beta": false});
+    ///     match request.merge_patch(&mut settings) {
+    ///         Ok(()) => HttpResponse::new(200, Some(settings.to_string())),
+    ///         Err(_) => HttpResponse::new(400, None),
+    ///     }
+    /// });
+    /// server.listener(8080);
+    /// ```
+    pub fn merge_patch(&self, target: &mut Value) -> Result<(), serde_json::Error> {
+        let patch: Value = serde_json::from_slice(&self.body)?;
+        merge_patch(target, &patch);
+        Ok(())
+    }
+}
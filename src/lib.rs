@@ -1,24 +1,117 @@
 use std::{
+    borrow::Cow,
     collections::HashMap,
+    fmt,
     io::{BufRead, BufReader, Error, ErrorKind, Read, Write},
-    net::{SocketAddr, TcpListener, TcpStream},
-    sync::{Arc, RwLock},
+    net::{
+        IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs,
+    },
+    ops::Deref,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Arc, Mutex, PoisonError, RwLock, RwLockReadGuard,
+    },
     thread,
+    time::{Duration, Instant},
 };
 
+use socket2::{Domain, Protocol, SockRef, Socket, TcpKeepalive, Type};
+
+/// Logs a server-side error through `tracing` when the feature is enabled, or to stderr otherwise.
+macro_rules! log_error {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        tracing::error!($($arg)*);
+        #[cfg(not(feature = "tracing"))]
+        eprintln!($($arg)*);
+    }};
+}
+
+/// Logs a routine connection event through `tracing` at debug level; without the feature it
+/// is discarded.
+macro_rules! log_debug {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+        #[cfg(not(feature = "tracing"))]
+        let _ = format_args!($($arg)*);
+    }};
+}
+
+/// Logs a client misbehaving through `tracing` at warn level; without the feature it is
+/// discarded, as clients are not the operator's to fix.
+macro_rules! log_warn {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        tracing::warn!($($arg)*);
+        #[cfg(not(feature = "tracing"))]
+        let _ = format_args!($($arg)*);
+    }};
+}
+
+mod access_log;
+#[cfg(feature = "tokio")]
+mod async_server;
+mod body;
+mod chain;
+mod cookie;
+mod counters;
+mod date;
+#[cfg(feature = "event-loop")]
+mod event_loop;
+#[cfg(feature = "json")]
+mod json;
+mod metrics;
+mod overload;
+mod params;
+mod proxy;
+mod query;
+mod response;
+mod router;
+mod session;
+mod static_files;
+#[cfg(feature = "testing")]
+mod testing;
+mod upload;
+
+pub use access_log::LogFormat;
+#[cfg(feature = "tokio")]
+pub use async_server::AsyncServer;
+pub use body::BodyError;
+pub use chain::Chain;
+pub use cookie::{Cookie, SameSite};
+pub use counters::Counters;
+#[cfg(feature = "json")]
+pub use json::merge_patch;
+pub use metrics::Metrics;
+pub use overload::{Load, OverloadPolicy};
+pub use params::Params;
+pub use query::Query;
+pub use response::IntoResponse;
+pub use router::{HashRouter, Match, Route, RouteTable, Router, TrieRouter};
+pub use session::{MemoryStore, Session, SessionStore};
+pub use static_files::{CacheControl, StaticDir};
+#[cfg(feature = "testing")]
+pub use testing::{TestClient, TestResponse};
+pub use upload::{BodyReader, SpooledBody};
+
 /// A simple HTTP server implementation.
 ///
 /// # Example
 ///
 /// Create a server instance and register a GET route:
 ///
-/// ```rust
-/// use guest_server::{Server,HttpResponse};
+/// ```rust,no_run
+/// use guest_server::{Server,HttpResponse,Request};
 ///
 /// let mut server = Server::new();
 /// server.get("/", home);
 ///
-/// fn home(query_params: Option<Vec<u8>>) -> HttpResponse {
+/// fn home(request: &Request) -> HttpResponse {
 ///       HttpResponse::new(200, Some("Hello, World!".to_string()))
 /// }
 ///
@@ -29,29 +122,371 @@ use std::{
 /// This simple example shows how to create a 'Server' instance.
 /// Registers a GET route and simulates an HTTP request to obtain the response.
 
-#[derive(Eq, PartialEq, Hash, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
 pub enum HttpMethod {
     GET,
+    HEAD,
     POST,
+    PATCH,
+    /// Answered with 405 unless enabled with `Server::trace`; never passed to routes.
+    TRACE,
+    /// Answered with 405 unless tunnels are enabled with `Server::tunnel`; never passed to
+    /// routes. The request path holds the target authority, e.g. `example.com:443`.
+    CONNECT,
+    /// Answered with 204 and an `Allow` header listing the path's methods, for paths with
+    /// routes, or for `*`, the server as a whole; other paths get 404 (or the fallback).
+    OPTIONS,
+}
+
+impl HttpMethod {
+    /// Returns the method's name as sent on the request line, e.g. `GET`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use guest_server::HttpMethod;
+    ///
+    /// assert_eq!(HttpMethod::PATCH.as_str(), "PATCH");
+    /// assert_eq!(HttpMethod::OPTIONS.to_string(), "OPTIONS");
+    /// assert_eq!("POST".parse(), Ok(HttpMethod::POST));
+    /// assert!("post".parse::<HttpMethod>().is_err()); // Method names are case-sensitive.
+    /// ```
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HttpMethod::GET => "GET",
+            HttpMethod::HEAD => "HEAD",
+            HttpMethod::POST => "POST",
+            HttpMethod::PATCH => "PATCH",
+            HttpMethod::TRACE => "TRACE",
+            HttpMethod::CONNECT => "CONNECT",
+            HttpMethod::OPTIONS => "OPTIONS",
+        }
+    }
+}
+
+impl fmt::Display for HttpMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+impl FromStr for HttpMethod {
+    type Err = UnknownMethod;
+
+    /// Parses a method name as sent on the request line; names are case-sensitive.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "GET" => Ok(HttpMethod::GET),
+            "HEAD" => Ok(HttpMethod::HEAD),
+            "POST" => Ok(HttpMethod::POST),
+            "PATCH" => Ok(HttpMethod::PATCH),
+            "TRACE" => Ok(HttpMethod::TRACE),
+            "CONNECT" => Ok(HttpMethod::CONNECT),
+            "OPTIONS" => Ok(HttpMethod::OPTIONS),
+            _ => Err(UnknownMethod),
+        }
+    }
+}
+
+/// The error parsing an `HttpMethod` from a name that is not one of its methods.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnknownMethod;
+
+impl fmt::Display for UnknownMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("unknown HTTP method")
+    }
+}
+
+impl std::error::Error for UnknownMethod {}
+
+/// What `Server::listener` does with new connections while the connection limit set with
+/// `Server::max_connections` is reached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AtCapacity {
+    /// Stop accepting until a connection closes. New clients wait in the kernel's listen
+    /// backlog, and are refused by the kernel once it is full.
+    Wait,
+    /// Accept the connection, answer 503 Service Unavailable and close it.
+    Reject,
+}
+
+/// A route handler, as stored by a `Router`.
+pub type Handler = Arc<dyn Fn(&Request) -> HttpResponse + Send + Sync + 'static>;
+
+/// Renders the body of an error response from its status code, as set with
+/// `Server::error_body`.
+type ErrorBody = Arc<dyn Fn(u16) -> HttpResponse + Send + Sync + 'static>;
+
+/// Rewrites request paths before routing, as set with `Server::path_rewrite`.
+type PathRewrite = Arc<dyn Fn(&str) -> Cow<'_, str> + Send + Sync + 'static>;
+
+/// Notified of every 5xx response, as set with `Server::on_error`.
+type OnError = Arc<dyn Fn(&Request, &HttpResponse) + Send + Sync + 'static>;
+
+/// Computes the trailer fields sent after a chunked body, as set with
+/// `HttpResponse::with_trailers`.
+type Trailers = Box<dyn FnOnce() -> Vec<(String, String)> + Send + 'static>;
+
+/// Builds the 405 response for a path and its allowed methods, as set with
+/// `Server::method_not_allowed`.
+type MethodNotAllowed = Arc<dyn Fn(&str, &[HttpMethod]) -> HttpResponse + Send + Sync + 'static>;
+
+/// The router, as shared between the server and its connections.
+enum Routes<R> {
+    /// Behind a lock, so routes can still be added while the server is serving.
+    Shared(Arc<RwLock<R>>),
+    /// Fixed with `Server::freeze`, so requests look routes up without locking.
+    Frozen(Arc<R>),
+}
+
+impl<R> Clone for Routes<R> {
+    fn clone(&self) -> Self {
+        match self {
+            Routes::Shared(router) => Routes::Shared(Arc::clone(router)),
+            Routes::Frozen(router) => Routes::Frozen(Arc::clone(router)),
+        }
+    }
+}
+
+impl<R> Routes<R> {
+    /// Gives access to the router for a lookup, locking it unless it is frozen.
+    fn read(&self) -> RoutesGuard<'_, R> {
+        match self {
+            Routes::Shared(router) => {
+                RoutesGuard::Shared(router.read().unwrap_or_else(PoisonError::into_inner))
+            }
+            Routes::Frozen(router) => RoutesGuard::Frozen(router),
+        }
+    }
+}
+
+/// Access to the router for the duration of a lookup.
+enum RoutesGuard<'a, R> {
+    Shared(RwLockReadGuard<'a, R>),
+    Frozen(&'a R),
+}
+
+impl<R> Deref for RoutesGuard<'_, R> {
+    type Target = R;
+
+    fn deref(&self) -> &R {
+        match self {
+            RoutesGuard::Shared(router) => router,
+            RoutesGuard::Frozen(router) => router,
+        }
+    }
+}
+
+/// Server-wide settings, shared read-only with every connection once the server is listening.
+#[derive(Clone)]
+struct Config {
+    default_headers: HashMap<String, String>, // Headers added to every response unless set by the handler.
+    max_request_line: usize, // Longest accepted request line in bytes, excluding the CRLF.
+    max_body: usize,         // Largest request body read into memory, by its `Content-Length`.
+    read_timeout: Option<Duration>, // Longest wait for the client to send more of its request.
+    request_deadline: Option<Duration>, // Longest time to receive a request's head and body.
+    write_timeout: Option<Duration>, // Longest wait for the client to accept more of the response.
+    cookie_secret: Option<Vec<u8>>, // Key for signing and verifying signed cookies.
+    #[cfg(feature = "signed-cookies")]
+    sessions: Option<session::Sessions>, // Where sessions are kept, if enabled.
+    metrics: Option<Arc<Metrics>>, // Collects request metrics, if enabled.
+    active_connections: Arc<AtomicUsize>, // Connections currently open, across all listeners.
+    answering: Arc<AtomicUsize>, // Requests from their first byte until their response is sent.
+    counters: Arc<Counters>, // Connections and requests handled, across all listeners.
+    shed_load: Option<usize>, // Requests served at once beyond which new ones are refused.
+    shed_response: Option<Handler>, // Answers refused requests; 503 with `Retry-After` if unset.
+    access_log: Option<access_log::AccessLog>, // Where a line per request is written, if enabled.
+    fallback: Option<Handler>, // Answers requests no route matches, instead of a 404.
+    proxies: Vec<proxy::Proxy>, // Path prefixes forwarded to upstream servers.
+    static_dirs: Vec<StaticDir>, // Directories served under path prefixes no route claims.
+    uploads: Vec<upload::Upload>, // Routes reading their request body themselves.
+    handler_timeout: Option<Duration>, // Longest a handler may run before 503 is sent.
+    max_connections: Option<(usize, AtCapacity)>, // Cap on open connections, and what to do at it.
+    max_requests: usize,     // Requests served on one connection before it is closed.
+    error_body: Option<ErrorBody>, // Fills in the body of error responses sent without one.
+    on_error: Option<OnError>, // Notified of every 5xx response to a parsed request.
+    default_type: Option<Option<String>>, // Replaces the `text/plain` default of `HttpResponse::new`.
+    method_not_allowed: Option<MethodNotAllowed>, // Builds 405 responses; bodiless if unset.
+    ipv6_only: Option<bool>, // IPV6_V6ONLY for IPv6 listeners; the OS default if unset.
+    overload: Option<Arc<dyn OverloadPolicy>>, // Turns new connections away with 503 when busy.
+    trace: bool,             // Whether TRACE requests are echoed back rather than refused with 405.
+    normalize_paths: bool,   // Whether empty segments and trailing slashes are removed from paths.
+    path_rewrite: Option<PathRewrite>, // Rewrites request paths before routing.
+    tunnel: Option<proxy::TunnelFilter>, // Targets CONNECT may tunnel to; refused with 405 if unset.
+    tcp_keepalive: Option<Duration>,     // Idle time before TCP keepalive probes start, if enabled.
+    #[cfg(feature = "compression")]
+    max_decompressed_body: usize, // Largest request body accepted after decompression.
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            default_headers: HashMap::new(),
+            max_request_line: 8 * 1024,
+            max_body: 16 * 1024 * 1024,
+            max_requests: 1000,
+            read_timeout: None,
+            request_deadline: None,
+            write_timeout: None,
+            cookie_secret: None,
+            #[cfg(feature = "signed-cookies")]
+            sessions: None,
+            metrics: None,
+            active_connections: Arc::new(AtomicUsize::new(0)),
+            answering: Arc::new(AtomicUsize::new(0)),
+            counters: Arc::default(),
+            shed_load: None,
+            shed_response: None,
+            access_log: None,
+            fallback: None,
+            proxies: Vec::new(),
+            static_dirs: Vec::new(),
+            uploads: Vec::new(),
+            handler_timeout: None,
+            max_connections: None,
+            error_body: None,
+            on_error: None,
+            default_type: None,
+            method_not_allowed: None,
+            ipv6_only: None,
+            overload: None,
+            trace: false,
+            normalize_paths: false,
+            path_rewrite: None,
+            tunnel: None,
+            tcp_keepalive: None,
+            #[cfg(feature = "compression")]
+            max_decompressed_body: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// Reasons a request could not be turned into a `Request`.
+enum ParseError {
+    /// Reading failed or the input ended before a complete request; nothing can be answered.
+    Io(Error),
+    /// The request was read but cannot be served; answer it with this status code.
+    Status(u16),
+    /// The client opened with the HTTP/2 connection preface; answer with `HTTP2_REFUSAL` and
+    /// close the connection.
+    Http2,
 }
 
-type Routes = Arc<
-    RwLock<
-        HashMap<
-            (HttpMethod, String),
-            Arc<dyn Fn(Option<Vec<u8>>) -> HttpResponse + Send + Sync + 'static>,
-        >,
-    >,
->;
+/// Answers a client that opened a connection with the HTTP/2 preface, in HTTP/2 framing so
+/// it understands: an empty SETTINGS frame, which must come first, then a GOAWAY frame with
+/// the HTTP_1_1_REQUIRED error code, telling it to retry over HTTP/1.1.
+pub(crate) const HTTP2_REFUSAL: [u8; 26] = [
+    0, 0, 0, 0x4, 0, 0, 0, 0, 0, // SETTINGS, no settings, stream 0
+    0, 0, 8, 0x7, 0, 0, 0, 0, 0, // GOAWAY, 8 bytes, stream 0
+    0, 0, 0, 0, // Last stream processed: none
+    0, 0, 0, 0xd, // HTTP_1_1_REQUIRED
+];
+
+impl From<Error> for ParseError {
+    fn from(error: Error) -> Self {
+        ParseError::Io(error)
+    }
+}
 
 /// Represents an HTTP server.
 ///
 /// This server listens for incoming HTTP requests, dispatches them to the correct handler based on the
-/// method and path, and sends back appropriate HTTP responses. It supports GET and POST routes.
+/// method and path, and sends back appropriate HTTP responses. It supports GET, POST and PATCH routes;
+/// HEAD requests are answered by the matching GET route with the body omitted.
+///
+/// The headers of a HEAD response are those of the GET response, `Content-Length` included,
+/// so a client can learn the size of a resource without fetching it:
+///
+/// ```rust
+/// use guest_server::Server;
+/// use std::io::{Read, Write};
+/// use std::net::TcpStream;
+///
+/// let mut server = Server::new();
+/// server.get("/", |_| "Hello, World!");
+/// let handle = server.bind("127.0.0.1:0").unwrap();
+///
+/// let mut stream = TcpStream::connect(handle.local_addrs()[0]).unwrap();
+/// stream
+///     .write_all(b"HEAD / HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n")
+///     .unwrap();
+/// let mut response = String::new();
+/// stream.read_to_string(&mut response).unwrap();
+/// assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+/// assert!(response.contains("\r\nContent-Length: 13\r\n"));
+/// // Nothing follows the blank line ending the headers
+/// assert!(response.ends_with("\r\n\r\n"));
+///
+/// handle.shutdown();
+/// handle.join();
+/// ```
+///
+/// OPTIONS requests are answered by the server itself: with 204 No Content and an `Allow`
+/// header listing exactly the methods the path can be requested with, or 404 for a path
+/// without routes. `OPTIONS *` lists the methods of the server as a whole.
+///
+/// ```rust
+/// # #[cfg(feature = "testing")] {
+/// use guest_server::{Server, TestClient};
+///
+/// let mut server = Server::new();
+/// server.get("/users", |_| "[]");
+/// server.post("/users", |_| "created");
+/// let handle = server.bind("127.0.0.1:0").unwrap();
+///
+/// let client = TestClient::new(handle.local_addrs()[0]);
+/// let response = client.request("OPTIONS", "/users", &[], b"").unwrap();
+/// assert_eq!(response.status_code, 204);
+/// assert_eq!(response.headers["allow"], "GET, HEAD, POST, OPTIONS");
+/// let response = client.request("OPTIONS", "/missing", &[], b"").unwrap();
+/// assert_eq!(response.status_code, 404);
+///
+/// handle.shutdown();
+/// handle.join();
+/// # }
+/// ```
 ///
 /// The server is multi-threaded, handling each incoming connection in a new thread.
-pub struct Server {
-    routes: Routes, // A map storing routes and their associated handler functions.
+///
+/// Routes are matched by a `TrieRouter`, so route paths may contain `:param` segments and a
+/// trailing `*wildcard`, unless another `Router` is given to `Server::with_router`.
+///
+/// State shared between connections (the router, sessions, metrics, the access log) sits
+/// behind locks. A panic while one is held poisons it; the server then carries on with the
+/// state as the panic left it instead of failing every later request, so one bad request
+/// cannot take the whole server down. The request whose handler panicked is answered with
+/// 500 Internal Server Error, and its connection closed.
+///
+/// ```rust
+/// # #[cfg(feature = "testing")] {
+/// use guest_server::{Server, TestClient};
+///
+/// let mut server = Server::new();
+/// server.get("/broken", |_| -> &'static str { panic!("bug") });
+/// server.get("/", |_| "fine");
+/// let handle = server.bind("127.0.0.1:0").unwrap();
+///
+/// let client = TestClient::new(handle.local_addrs()[0]);
+/// let response = client.get("/broken").unwrap();
+/// assert_eq!(response.status_code, 500);
+/// assert_eq!(response.headers["connection"], "close");
+/// assert_eq!(client.get("/").unwrap().text(), "fine");
+///
+/// handle.shutdown();
+/// handle.join();
+/// # }
+/// ```
+pub struct Server<R: Router = TrieRouter> {
+    routes: Routes<R>, // The router holding routes and their associated handler functions.
+    route_table: RouteTable, // The method and path of each registered route, for listing.
+    config: Config,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Server {
@@ -60,255 +495,4107 @@ impl Server {
     /// # Returns
     /// A new instance of `Server` with an empty route configuration.
     pub fn new() -> Self {
+        Server::with_router(TrieRouter::new())
+    }
+
+    /// Creates a server that permanently redirects every request to HTTPS.
+    ///
+    /// Meant to run on the plain HTTP port next to the HTTPS deployment. Each request is
+    /// answered with 301 Moved Permanently to the same host, path and query string under the
+    /// `https` scheme. Requests without a `Host` header cannot be redirected and get 400.
+    ///
+    /// # Parameters
+    /// - 'https_port' : The port HTTPS is served on, or `None` for the default port 443.
+    ///
+    /// # Returns
+    /// A new `Server` whose fallback issues the redirect; routes may still be added to it,
+    /// e.g. for ACME challenges that must stay on HTTP.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use guest_server::Server;
+    ///
+    /// // http://example.com/a?b=1 -> https://example.com:8443/a?b=1
+    /// Server::https_redirect(Some(8443)).listener(80);
+    /// ```
+    pub fn https_redirect(https_port: Option<u16>) -> Self {
+        let mut server = Server::new();
+        server.fallback(move |request| {
+            let Some(host) = request.headers.get("host") else {
+                return HttpResponse::status(400);
+            };
+            // Drop the port from the host, minding the brackets of an IPv6 literal
+            let host = match host.rfind(':') {
+                Some(colon) if !host[colon..].contains(']') => &host[..colon],
+                _ => host.as_str(),
+            };
+            let port = match https_port {
+                Some(port) if port != 443 => format!(":{}", port),
+                _ => String::new(),
+            };
+            let mut location = format!("https://{}{}{}", host, port, request.path);
+            if !request.query.as_str().is_empty() {
+                location.push('?');
+                location.push_str(request.query.as_str());
+            }
+            HttpResponse::redirect(301, &location)
+        });
+        server
+    }
+}
+
+impl<R: Router> Server<R> {
+    /// Creates and initializes a new server instance that matches routes with the given router.
+    ///
+    /// # Parameters
+    /// - 'router' : The routing strategy; routes registered with `get`, `post` etc. are added
+    ///   to it.
+    ///
+    /// # Returns
+    /// A new instance of `Server` using `router`.
+    pub fn with_router(router: R) -> Self {
         Self {
-            routes: Arc::new(RwLock::new(HashMap::new())),
+            routes: Routes::Shared(Arc::new(RwLock::new(router))),
+            route_table: RouteTable::default(),
+            config: Config::default(),
         }
     }
 
-    /// Registers a route with a specific HTTP method, path, and handler.
+    /// Adds or updates a header sent with every response, such as a security header.
+    ///
+    /// A header of the same name set by the handler takes precedence over the default.
     ///
     /// # Parameters
-    /// - 'method' : The HTTP method (GET, POST) for this route.
-    /// - 'path' : The route path (e.g., '/home').
-    /// - 'handler' : The closure that processes the request for this path.
-    fn route<F>(&mut self, method: HttpMethod, path: &str, handler: F)
-    where
-        F: Fn(Option<Vec<u8>>) -> HttpResponse + Send + Sync + 'static,
-    {
-        self.routes
-            .write()
-            .unwrap()
-            .insert((method, path.to_string()), Arc::new(handler));
+    /// - 'key' : The header key.
+    /// - 'value' : The header value.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use guest_server::Server;
+    ///
+    /// let mut server = Server::new();
+    /// server.default_header("X-Content-Type-Options", "nosniff");
+    /// server.default_header("X-Frame-Options", "DENY");
+    /// ```
+    pub fn default_header(&mut self, key: &str, value: &str) {
+        self.config
+            .default_headers
+            .insert(key.to_string(), value.to_string());
     }
 
-    /// Registers a GET route with a specified path and handler.
+    /// Sets the maximum length of the request line (method, request target and version).
+    ///
+    /// Requests with a longer request line are answered with 414 URI Too Long before the line
+    /// is fully buffered. The default is 8 KiB.
     ///
     /// # Parameters
-    /// - 'path' : The route path to register, e.g., '/home'.
-    /// - 'handler' : The closure that processes the request for this path.
-    pub fn get<F>(&mut self, path: &str, handler: F)
-    where
-        F: Fn(Option<Vec<u8>>) -> HttpResponse + Send + Sync + 'static,
-    {
-        self.route(HttpMethod::GET, path, handler);
+    /// - 'length' : The maximum request line length in bytes, excluding the trailing CRLF.
+    pub fn max_request_line(&mut self, length: usize) {
+        self.config.max_request_line = length;
     }
 
-    /// Registers a POST route with a specified path and handler.
+    /// Sets the largest request body read into memory for a handler.
+    ///
+    /// A request whose `Content-Length` is larger is answered with 413 Payload Too Large
+    /// before any of its body is read, and the connection is closed. The default is 16 MiB.
+    /// Routes added with `Server::post_streaming`, `Server::post_lazy` and
+    /// `Server::post_spooled` read their body as it arrives and are not bound by it.
     ///
     /// # Parameters
-    /// - 'path' : The route path to register, e.g., '/submit'.
-    /// - 'handler' : The closure that processes the request for this path.
+    /// - 'length' : The maximum body length in bytes.
     ///
     /// # Example
     ///
     /// ```rust
-    /// use guest_server::{Server,HttpResponse};
+    /// # #[cfg(feature = "testing")] {
+    /// use guest_server::{Server, TestClient};
+    /// use std::io::{Read, Write};
+    /// use std::net::TcpStream;
+    ///
     /// let mut server = Server::new();
-    /// server.post("/submit",submit);
-    /// fn submit(body: Option<Vec<u8>>) -> HttpResponse {
-    ///     HttpResponse::new(200, Some("{\"key\":\"value\"}".to_string())).insert_header("Content-Type","application/json")
-    /// }
-    /// server.listener(8080);
+    /// server.post("/notes", |request| format!("{} bytes", request.body.len()));
+    /// server.max_body(1024);
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let client = TestClient::new(handle.local_addrs()[0]);
+    /// assert_eq!(client.post("/notes", &[b'x'; 1024]).unwrap().text(), "1024 bytes");
+    /// assert_eq!(client.post("/notes", &[b'x'; 1025]).unwrap().status_code, 413);
+    ///
+    /// // Refused by its length alone, without waiting for the body
+    /// let mut stream = TcpStream::connect(handle.local_addrs()[0]).unwrap();
+    /// let head = "POST /notes HTTP/1.1\r\nHost: test\r\n\
+    ///             Content-Length: 99999999999999\r\n\r\n";
+    /// stream.write_all(head.as_bytes()).unwrap();
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response).unwrap();
+    /// assert!(response.starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+    /// assert!(response.contains("Connection: close\r\n"));
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// # }
     /// ```
-    pub fn post<F>(&mut self, path: &str, handler: F)
-    where
-        F: Fn(Option<Vec<u8>>) -> HttpResponse + Send + Sync + 'static,
-    {
-        self.route(HttpMethod::POST, path, handler);
+    pub fn max_body(&mut self, length: usize) {
+        self.config.max_body = length;
     }
 
-    /// Starts the server and listens for incoming connections on the specified port.
+    /// Sets how long a connection may wait for the client to send more of its request.
+    ///
+    /// A client that stalls for longer while sending the request line, headers or body has its
+    /// connection closed without a response, so slowly trickled requests cannot tie up a
+    /// worker thread. There is no limit by default. Applies to `Server::listener` and
+    /// `Server::bind`.
     ///
     /// # Parameters
-    /// - 'port' : The port number to listen on.
-    pub fn listener(&self, port: u16) {
-        let addr: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
-        let listener = TcpListener::bind(addr).unwrap();
-        // Listen for incoming connections
-        for stream in listener.incoming() {
-            match stream {
-                Ok(stream) => {
-                    let routes = Arc::clone(&self.routes);
-                    thread::spawn(move || {
-                        if let Err(e) = Server::handle_connection(routes, stream) {
-                            eprintln!("Connection failed: {}", e);
-                        }
-                    });
-                }
-                Err(e) => eprintln!("Failed to accept connection: {}", e),
-            }
-        }
+    /// - 'timeout' : The longest time a single read may block.
+    pub fn read_timeout(&mut self, timeout: Duration) {
+        self.config.read_timeout = Some(timeout);
     }
 
-    /// Handles the incoming TCP connection, processes the HTTP request, and sends back a response.
+    /// Sets how long a client may take to send a whole request, from its first byte to the
+    /// end of its headers and body.
+    ///
+    /// `read_timeout` bounds each read, so a client trickling a byte at a time just within it
+    /// can hold a connection forever; the deadline bounds the request as a whole. A request
+    /// not received in time is answered with 408 Request Timeout and the connection closed.
+    /// Waiting for the next request on a kept-alive connection is not counted, nor is the
+    /// body of a `post_streaming` route, which the handler reads. There is no deadline by
+    /// default. Applies to `Server::listener` and `Server::bind`.
     ///
     /// # Parameters
-    /// - `routes`: The `Routes` object containing the routing information. This is used to match the
-    ///   incoming HTTP request's path and method to the appropriate handler function.
-    /// - `stream`: The TCP stream representing the connection to the client. This is used to read
-    ///   the request and send the response back to the client. The stream is mutable because it will
-    ///   be written to as part of generating the HTTP response.
-    fn handle_connection(routes: Routes, mut stream: TcpStream) -> Result<(), Error> {
-        let mut reader = BufReader::new(&stream);
-        let mut buffer_request = Vec::new();
-        let mut header_parsed = false;
-        let mut content_length = 0;
-        let mut method = Option::None;
-        let mut path = String::new();
+    /// - 'deadline' : The longest time to receive a request.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use guest_server::Server;
+    /// use std::io::{Read, Write};
+    /// use std::net::TcpStream;
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// let mut server = Server::new();
+    /// server.read_timeout(Duration::from_secs(5));
+    /// server.request_deadline(Duration::from_millis(200));
+    /// server.get("/", |_| "Hello, World!");
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// // Each byte arrives well within the read timeout, but the request takes too long
+    /// let mut stream = TcpStream::connect(handle.local_addrs()[0]).unwrap();
+    /// for byte in b"GET / HTTP/1.1\r\nHost: test\r\n" {
+    ///     if stream.write_all(&[*byte]).is_err() {
+    ///         break;
+    ///     }
+    ///     thread::sleep(Duration::from_millis(20));
+    /// }
+    /// let mut response = String::new();
+    /// let _ = stream.read_to_string(&mut response);
+    /// assert!(response.starts_with("HTTP/1.1 408 Request Timeout\r\n"));
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// ```
+    pub fn request_deadline(&mut self, deadline: Duration) {
+        self.config.request_deadline = Some(deadline);
+    }
 
-        loop {
-            let mut line = String::new();
-            let bytes_read = reader.read_line(&mut line)?;
+    /// Sets how long a connection may wait for the client to accept more of the response.
+    ///
+    /// A client that reads the response too slowly, or not at all, has its connection closed
+    /// once a single write blocks for longer, defending against "slow read" attacks. There is
+    /// no limit by default. Applies to `Server::listener` and `Server::bind`.
+    ///
+    /// # Parameters
+    /// - 'timeout' : The longest time a single write may block.
+    pub fn write_timeout(&mut self, timeout: Duration) {
+        self.config.write_timeout = Some(timeout);
+    }
 
-            if bytes_read == 0 {
-                break;
-            }
+    /// Sets the secret used to sign cookies marked with `Cookie::signed` and to verify them
+    /// when they come back.
+    ///
+    /// Signed cookies carry an HMAC-SHA256 of their name and value, so clients can read but
+    /// not forge them. Use a long random secret and keep it stable across restarts, or every
+    /// previously issued signed cookie becomes invalid. Requires the `signed-cookies` feature.
+    ///
+    /// # Parameters
+    /// - 'secret' : The signing key.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use guest_server::{Cookie,HttpResponse,Server};
+    ///
+    /// let mut server = Server::new();
+    /// server.cookie_secret(b"a long, random and private secret");
+    /// server.get("/login", |_| {
+    ///     HttpResponse::new(200, None).set_cookie(Cookie::new("user", "42").http_only(true).signed())
+    /// });
+    /// server.get("/me", |request| match request.signed_cookies.get("user") {
+    ///     Some(user) => HttpResponse::new(200, Some(user.clone())),
+    ///     None => HttpResponse::new(401, None),
+    /// });
+    /// server.listener(8080);
+    /// ```
+    ///
+    /// A signed cookie comes back only as it was sent:
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "testing")] {
+    /// use guest_server::{Cookie, HttpResponse, Server, TestClient};
+    ///
+    /// let mut server = Server::new();
+    /// server.cookie_secret(b"a long, random and private secret");
+    /// server.get("/login", |_| {
+    ///     HttpResponse::status(204).set_cookie(Cookie::new("user", "42").signed())
+    /// });
+    /// server.get("/me", |request| match request.signed_cookies.get("user") {
+    ///     Some(user) => HttpResponse::from_string(200, user.clone()),
+    ///     None => HttpResponse::status(401),
+    /// });
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let client = TestClient::new(handle.local_addrs()[0]);
+    /// let login = client.get("/login").unwrap();
+    /// let cookie = login.headers["set-cookie"].clone();
+    /// let (value, signature) = cookie.strip_prefix("user=").unwrap().split_once('.').unwrap();
+    /// assert_eq!(value, "42");
+    /// assert_eq!(signature.len(), 64);
+    ///
+    /// let me = |cookie: &str| client.request("GET", "/me", &[("Cookie", cookie)], b"").unwrap();
+    /// let response = me(&cookie);
+    /// assert_eq!(response.text(), "42");
+    /// // An altered value, an altered signature, or no signature at all is not let through
+    /// assert_eq!(me(&cookie.replace("user=42.", "user=43.")).status_code, 401);
+    /// let forged = format!("user=42.{}", signature.replace(|c| c != '0', "0"));
+    /// assert_eq!(me(&forged).status_code, 401);
+    /// assert_eq!(me("user=42").status_code, 401);
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// # }
+    /// ```
+    #[cfg(feature = "signed-cookies")]
+    pub fn cookie_secret(&mut self, secret: &[u8]) {
+        self.config.cookie_secret = Some(secret.to_vec());
+    }
 
-            buffer_request.extend_from_slice(line.as_bytes());
+    /// Enables sessions, exposed to handlers as `request.session`.
+    ///
+    /// A session is identified by a signed `session_id` cookie, so a cookie secret must also be
+    /// set with `Server::cookie_secret`. Sessions expire `ttl` after the last request that used
+    /// them. Requires the `signed-cookies` feature.
+    ///
+    /// # Parameters
+    /// - 'store' : Where session data is kept, e.g. `MemoryStore::new()`.
+    /// - 'ttl' : How long an unused session is kept.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use guest_server::{HttpResponse,MemoryStore,Server};
+    /// use std::time::Duration;
+    ///
+    /// let mut server = Server::new();
+    /// server.cookie_secret(b"a long, random and private secret");
+    /// server.sessions(MemoryStore::new(), Duration::from_secs(30 * 60));
+    /// server.get("/visit", |request| {
+    ///     let visits = request.session.get("visits").and_then(|v| v.parse().ok()).unwrap_or(0) + 1;
+    ///     request.session.insert("visits", &visits.to_string());
+    ///     HttpResponse::new(200, Some(format!("Visit number {}", visits)))
+    /// });
+    /// server.listener(8080);
+    /// ```
+    ///
+    /// The session follows the client through its cookie, until it is cleared:
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "testing")] {
+    /// use guest_server::{HttpResponse, MemoryStore, Server, TestClient};
+    /// use std::time::Duration;
+    ///
+    /// let mut server = Server::new();
+    /// server.cookie_secret(b"a long, random and private secret");
+    /// server.sessions(MemoryStore::new(), Duration::from_secs(30 * 60));
+    /// server.get("/visit", |request| {
+    ///     let visits = request.session.get("visits").map_or(0, |v| v.parse().unwrap()) + 1;
+    ///     request.session.insert("visits", &visits.to_string());
+    ///     format!("visit {}", visits)
+    /// });
+    /// server.get("/logout", |request| {
+    ///     request.session.clear();
+    ///     HttpResponse::status(204)
+    /// });
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let client = TestClient::new(handle.local_addrs()[0]);
+    /// let visit = |cookie: &str| {
+    ///     let headers = [("Cookie", cookie)];
+    ///     client.request("GET", "/visit", &headers, b"").unwrap()
+    /// };
+    /// let first = client.get("/visit").unwrap();
+    /// assert_eq!(first.text(), "visit 1");
+    /// let set_cookie = &first.headers["set-cookie"];
+    /// assert!(set_cookie.starts_with("session_id="));
+    /// assert!(set_cookie.ends_with("; Path=/; Max-Age=1800; HttpOnly; SameSite=Lax"));
+    /// let cookie = set_cookie.split(';').next().unwrap().to_string();
+    /// assert_eq!(visit(&cookie).text(), "visit 2");
+    ///
+    /// // A session ID altered by the client starts a new session
+    /// let tampered = cookie.replacen("session_id=", "session_id=0", 1);
+    /// let response = visit(&tampered);
+    /// assert_eq!(response.text(), "visit 1");
+    /// assert_ne!(response.headers["set-cookie"].split(';').next().unwrap(), cookie);
+    ///
+    /// // Clearing the session expires the cookie and forgets its data
+    /// let logout = client.request("GET", "/logout", &[("Cookie", &cookie)], b"").unwrap();
+    /// assert_eq!(logout.headers["set-cookie"], "session_id=; Path=/; Max-Age=0");
+    /// assert_eq!(visit(&cookie).text(), "visit 1");
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// # }
+    /// ```
+    #[cfg(feature = "signed-cookies")]
+    pub fn sessions<S: SessionStore + 'static>(&mut self, store: S, ttl: Duration) {
+        self.config.sessions = Some(session::Sessions {
+            store: Arc::new(store),
+            ttl,
+        });
+    }
 
-            if line == "\r\n" {
-                header_parsed = true;
-                break;
-            }
+    /// Enables request metrics and returns the collector.
+    ///
+    /// Every request served afterwards is counted by method, path and status code, and its
+    /// latency is recorded in a histogram. Requests served by a route are counted under the
+    /// route's pattern, e.g. `/users/:id`, rather than each path it matched, and proxied
+    /// requests under the proxy's prefix. Every other request, e.g. one answered with 404,
+    /// is counted under an empty path, so clients cannot add series by making up paths.
+    /// Serve `Metrics::render` from a route to let Prometheus scrape them. Calling this again
+    /// returns the same collector.
+    ///
+    /// # Returns
+    /// A shared handle to the metrics collector.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "testing")] {
+    /// use guest_server::{Server, TestClient};
+    ///
+    /// let mut server = Server::new();
+    /// let metrics = server.metrics();
+    /// server.get("/users/:id", |request| request.params["id"].clone());
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let client = TestClient::new(handle.local_addrs()[0]);
+    /// for path in ["/users/1", "/users/2", "/missing/1", "/missing/2"] {
+    ///     client.get(path).unwrap();
+    /// }
+    /// let rendered = metrics.render();
+    /// assert!(rendered.contains(r#"{method="GET",path="/users/:id",status="200"} 2"#));
+    /// assert!(rendered.contains(r#"{method="GET",path="",status="404"} 2"#));
+    /// assert!(!rendered.contains("/missing"));
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// # }
+    /// ```
+    pub fn metrics(&mut self) -> Arc<Metrics> {
+        Arc::clone(
+            self.config
+                .metrics
+                .get_or_insert_with(|| Arc::new(Metrics::new())),
+        )
+    }
 
-            if line.starts_with("GET") || line.starts_with("POST") {
+    /// Sets the handler for requests that match no route, whatever their method and path.
+    ///
+    /// Without a fallback such requests are answered with 404 Not Found. Requests whose path
+    /// has routes for other methods get 405 instead, see `method_not_allowed`.
+    ///
+    /// # Parameters
+    /// - 'handler' : The closure that processes unmatched requests.
+    pub fn fallback<F, T>(&mut self, handler: F)
+    where
+        F: Fn(&Request) -> T + Send + Sync + 'static,
+        T: IntoResponse,
+    {
+        self.config.fallback = Some(Arc::new(move |request| handler(request).into_response()));
+    }
+
+    /// Sets how the body of error responses is rendered when none was given.
+    ///
+    /// Every response with a 4xx or 5xx status and no body, whether from a handler or from the
+    /// server itself (e.g. 404 for an unknown path or 400 for a malformed request), gets the
+    /// body and headers, such as `Content-Type`, of the response `render` returns for its
+    /// status code. Responses whose handler set a body are sent as they are.
+    ///
+    /// # Parameters
+    /// - 'render' : The closure that builds the error response for a status code.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use guest_server::{HttpResponse,Server};
+    ///
+    /// let mut server = Server::new();
+    /// server.error_body(|status_code| {
+    ///     let message = format!("<h1>{}</h1>", status_code);
+    ///     HttpResponse::with_content_type(status_code, "text/html", Some(message))
+    /// });
+    /// server.listener(8080);
+    /// ```
+    pub fn error_body<F>(&mut self, render: F)
+    where
+        F: Fn(u16) -> HttpResponse + Send + Sync + 'static,
+    {
+        self.config.error_body = Some(Arc::new(render));
+    }
+
+    /// Sets a hook notified of every response with a 5xx status, e.g. to report server errors
+    /// to an alerting service without wrapping each handler.
+    ///
+    /// The hook sees the final response, whatever produced it: a handler, a handler that
+    /// panicked, one that timed out under `handler_timeout`, an unreachable proxy upstream,
+    /// or load shedding. It is not called for requests that could not be parsed, as there is no
+    /// request to report, for 5xx responses a proxy upstream sent, which are passed through
+    /// as they are, nor for responses below 500; the access log covers every request.
+    /// It runs on the connection's thread before the response is sent, so it should hand
+    /// slow work off rather than do it inline.
+    ///
+    /// # Parameters
+    /// - 'hook' : The closure called with the request and its response.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "testing")] {
+    /// use guest_server::{HttpResponse, Server, TestClient};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let errors = Arc::new(Mutex::new(Vec::new()));
+    /// let reported = Arc::clone(&errors);
+    /// let mut server = Server::new();
+    /// server.get("/", |_| "ok");
+    /// server.get("/broken", |_| HttpResponse::status(502));
+    /// server.on_error(move |request, response| {
+    ///     let error = format!("{} {}", response.status_code, request.path);
+    ///     reported.lock().unwrap().push(error);
+    /// });
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let client = TestClient::new(handle.local_addrs()[0]);
+    /// client.get("/").unwrap();
+    /// client.get("/missing").unwrap();
+    /// client.get("/broken").unwrap();
+    /// assert_eq!(*errors.lock().unwrap(), ["502 /broken"]);
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// # }
+    /// ```
+    pub fn on_error<F>(&mut self, hook: F)
+    where
+        F: Fn(&Request, &HttpResponse) + Send + Sync + 'static,
+    {
+        self.config.on_error = Some(Arc::new(hook));
+    }
+
+    /// Sets how requests are answered whose path has routes, but none for their method.
+    ///
+    /// Such requests get 405 Method Not Allowed rather than 404, or the fallback, with an
+    /// `Allow` header listing the path's methods comma-separated, HEAD included with GET and
+    /// OPTIONS always.
+    /// The handler replaces that response, e.g. to send a JSON body; if it sets no `Allow`
+    /// header, the default one is added.
+    ///
+    /// # Parameters
+    /// - 'handler' : The closure building the response from the request path and its allowed
+    ///   methods.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use guest_server::{HttpResponse,Server};
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/users", |_| "[]");
+    /// server.method_not_allowed(|path, allowed| {
+    ///     let methods: Vec<String> = allowed.iter().map(|m| format!("\"{}\"", m)).collect();
+    ///     let body = format!("{{\"path\":\"{}\",\"allowed\":[{}]}}", path, methods.join(","));
+    ///     HttpResponse::with_content_type(405, "application/json", Some(body))
+    /// });
+    /// server.listener(8080);
+    /// ```
+    pub fn method_not_allowed<F>(&mut self, handler: F)
+    where
+        F: Fn(&str, &[HttpMethod]) -> HttpResponse + Send + Sync + 'static,
+    {
+        self.config.method_not_allowed = Some(Arc::new(handler));
+    }
+
+    /// Sets the `Content-Type` of responses that do not choose one.
+    ///
+    /// `HttpResponse::new`, and with it handlers returning a `&str` or `String`, labels every
+    /// body `text/plain`, which is wrong for binary APIs and may lead clients to treat bytes
+    /// as text. This replaces that default for all responses, including the server's own
+    /// error responses, unless a `Content-Type` was set explicitly, e.g. with
+    /// `HttpResponse::with_content_type`, `insert_header` or `error_body`.
+    ///
+    /// # Parameters
+    /// - 'content_type' : The type to send instead, e.g. 'application/octet-stream', or `None`
+    ///   to send no `Content-Type` at all, so handlers must set it.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use guest_server::{HttpResponse,Server};
+    ///
+    /// let mut server = Server::new();
+    /// server.default_content_type(None);
+    /// // Sent without a Content-Type
+    /// server.get("/raw", |_| HttpResponse::new(200, Some("\u{1}\u{2}".to_string())));
+    /// // Sent as set
+    /// server.get("/text", |_| {
+    ///     HttpResponse::new(200, Some("hello".to_string())).insert_header("Content-Type", "text/plain")
+    /// });
+    /// server.listener(8080);
+    /// ```
+    pub fn default_content_type(&mut self, content_type: Option<&str>) {
+        self.config.default_type = Some(content_type.map(str::to_string));
+    }
+
+    /// Sets the policy deciding when the server is too busy to take a new connection.
+    ///
+    /// Every accepted connection is checked against the policy first; while it reports the
+    /// server overloaded, connections are answered with 503 Service Unavailable and
+    /// `Retry-After: 1` and closed. See `OverloadPolicy` for an example.
+    ///
+    /// # Parameters
+    /// - 'policy' : The policy, e.g. a closure taking a `&Load`.
+    pub fn overload_policy<P: OverloadPolicy>(&mut self, policy: P) {
+        self.config.overload = Some(Arc::new(policy));
+    }
+
+    /// Refuses new requests at once while too many are being served, rather than letting
+    /// them queue up behind the rest.
+    ///
+    /// A request arriving while `max_in_flight` requests are already being served, across
+    /// all connections, is answered with 503 Service Unavailable and `Retry-After: 1` without
+    /// running its handler, or with the response set with `shed_response`. Latency for the
+    /// requests that are served then stays in bounds during a spike. Unlike
+    /// `overload_policy`, which turns connections away, this applies to every request,
+    /// including those on kept-alive connections.
+    ///
+    /// # Parameters
+    /// - 'max_in_flight' : The most requests served at once.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "testing")] {
+    /// use guest_server::{Server, TestClient};
+    /// use std::sync::{mpsc, Mutex};
+    /// use std::thread;
+    ///
+    /// let (started, wait_started) = mpsc::channel();
+    /// let (release, wait_release) = mpsc::channel::<()>();
+    /// let (started, wait_release) = (Mutex::new(started), Mutex::new(wait_release));
+    ///
+    /// let mut server = Server::new();
+    /// server.shed_load(1);
+    /// server.get("/slow", move |_| {
+    ///     started.lock().unwrap().send(()).unwrap();
+    ///     wait_release.lock().unwrap().recv().unwrap();
+    ///     "done"
+    /// });
+    /// server.get("/", |_| "ok");
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    /// let client = TestClient::new(handle.local_addrs()[0]);
+    ///
+    /// let slow = thread::spawn(move || client.get("/slow").unwrap().text());
+    /// wait_started.recv().unwrap();
+    /// let shed = TestClient::new(handle.local_addrs()[0]).get("/").unwrap();
+    /// assert_eq!(shed.status_code, 503);
+    /// assert_eq!(shed.headers["retry-after"], "1");
+    ///
+    /// release.send(()).unwrap();
+    /// assert_eq!(slow.join().unwrap(), "done");
+    /// assert_eq!(TestClient::new(handle.local_addrs()[0]).get("/").unwrap().text(), "ok");
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// # }
+    /// ```
+    pub fn shed_load(&mut self, max_in_flight: usize) {
+        self.config.shed_load = Some(max_in_flight);
+    }
+
+    /// Sets the response to requests refused by `shed_load`, e.g. to suggest a longer
+    /// `Retry-After` or to send a body.
+    ///
+    /// # Parameters
+    /// - 'respond' : Builds the response from the refused request.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use guest_server::{HttpResponse,Server};
+    ///
+    /// let mut server = Server::new();
+    /// server.shed_load(256);
+    /// server.shed_response(|_| {
+    ///     HttpResponse::problem(503, "Busy", "Too many requests at once; try again shortly.")
+    ///         .insert_header("Retry-After", "5")
+    /// });
+    /// server.listener(8080);
+    /// ```
+    pub fn shed_response<F>(&mut self, respond: F)
+    where
+        F: Fn(&Request) -> HttpResponse + Send + Sync + 'static,
+    {
+        self.config.shed_response = Some(Arc::new(respond));
+    }
+
+    /// Sets whether TRACE requests are answered by echoing the request back.
+    ///
+    /// TRACE is refused with 405 Method Not Allowed by default: echoing requests lets a script
+    /// on another site read headers it cannot otherwise see (cross-site tracing). When enabled,
+    /// the request line and headers come back as a `message/http` body, leaving out
+    /// `Authorization`, `Cookie` and `Proxy-Authorization`. Routes never see TRACE requests
+    /// either way.
+    ///
+    /// # Parameters
+    /// - 'enabled' : Whether to echo TRACE requests.
+    pub fn trace(&mut self, enabled: bool) {
+        self.config.trace = enabled;
+    }
+
+    /// Sets whether request paths are normalized before routing.
+    ///
+    /// When enabled, empty segments are removed, so `/users//42/` is routed, and seen by
+    /// handlers, as `/users/42`. The root always stays `/`: `/`, `//` and an empty path, as in
+    /// `GET http://example.com HTTP/1.1`, all reach the route for `/`. `OPTIONS *` and the
+    /// `host:port` target of CONNECT are left as they are. Register routes without a trailing
+    /// slash then, since `/users/` can no longer match. Directories under `static_dir` are
+    /// served without redirecting to add a trailing slash.
+    ///
+    /// Disabled by default, paths are routed as sent, except that an empty path is taken as
+    /// `/`: `/users/` and `/users` are different routes and `//` matches neither `/` nor any
+    /// other static route.
+    ///
+    /// # Parameters
+    /// - 'enabled' : Whether to normalize request paths.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "testing")] {
+    /// use guest_server::{Server, TestClient};
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/", |_| "root");
+    /// server.get("/users", |_| "users");
+    /// server.normalize_paths(true);
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let client = TestClient::new(handle.local_addrs()[0]);
+    /// assert_eq!(client.get("/").unwrap().text(), "root");
+    /// assert_eq!(client.get("//").unwrap().text(), "root");
+    /// assert_eq!(client.get("/users/").unwrap().text(), "users");
+    /// assert_eq!(client.get("//users").unwrap().text(), "users");
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// # }
+    /// ```
+    pub fn normalize_paths(&mut self, enabled: bool) {
+        self.config.normalize_paths = enabled;
+    }
+
+    /// Sets how request paths are rewritten before routing, e.g. to strip the prefix the
+    /// application is mounted under behind a reverse proxy, or to lowercase paths.
+    ///
+    /// The rewrite runs once the request line is parsed, after `normalize_paths`, and its
+    /// result replaces `Request::path` for everything that follows: routes, upload routes,
+    /// proxies, static directories, handlers and the access log. An empty result is taken as
+    /// `/`. `OPTIONS *` and the `host:port` target of CONNECT are not rewritten. Return the
+    /// path unchanged, e.g. as `Cow::Borrowed`, to leave it as is.
+    ///
+    /// # Parameters
+    /// - 'rewrite' : The closure computing the path to route from the path sent.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "testing")] {
+    /// use guest_server::{Server, TestClient};
+    /// use std::borrow::Cow;
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/", |_| "home");
+    /// server.get("/users", |request| request.path.clone());
+    /// server.path_rewrite(|path| match path.strip_prefix("/app") {
+    ///     Some(rest) if rest.is_empty() || rest.starts_with('/') => Cow::Borrowed(rest),
+    ///     _ => Cow::Borrowed(path),
+    /// });
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let client = TestClient::new(handle.local_addrs()[0]);
+    /// assert_eq!(client.get("/app/users").unwrap().text(), "/users");
+    /// assert_eq!(client.get("/app").unwrap().text(), "home");
+    /// assert_eq!(client.get("/users").unwrap().text(), "/users");
+    /// assert_eq!(client.get("/apples").unwrap().status_code, 404);
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// # }
+    /// ```
+    pub fn path_rewrite<F>(&mut self, rewrite: F)
+    where
+        F: Fn(&str) -> Cow<'_, str> + Send + Sync + 'static,
+    {
+        self.config.path_rewrite = Some(Arc::new(rewrite));
+    }
+
+    /// Sets whether IPv6 addresses given to `bind` also accept IPv4 connections.
+    ///
+    /// With dual-stack enabled, binding the unspecified address `[::]` serves both IPv6 and
+    /// IPv4 clients on one socket, IPv4 clients appearing as IPv4-mapped addresses such as
+    /// `::ffff:192.0.2.1`. Disabled, an IPv6 socket only serves IPv6, so IPv4 can be bound on
+    /// the same port separately. Without this setting the operating system default applies,
+    /// which differs between platforms. IPv4 addresses are not affected.
+    ///
+    /// # Parameters
+    /// - 'enabled' : Whether IPv6 sockets accept IPv4 connections too.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use guest_server::{HttpResponse,Server};
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/", |_| HttpResponse::new(200, None));
+    /// server.dual_stack(true);
+    /// let handle = server.bind("[::]:8080").unwrap();
+    /// handle.join();
+    /// ```
+    pub fn dual_stack(&mut self, enabled: bool) {
+        self.config.ipv6_only = Some(!enabled);
+    }
+
+    /// Sends error responses without a body with a JSON body instead, such as
+    /// `{"error":"Not Found","status":404}` with `Content-Type: application/json`. Codes
+    /// without a standard reason phrase get `Client Error` or `Server Error` by their class.
+    ///
+    /// This is `error_body` with a JSON renderer; see there for which responses it applies to.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "testing")] {
+    /// use guest_server::{HttpResponse, Server, TestClient};
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/teapot", |_| HttpResponse::status(418));
+    /// server.get("/vendor", |_| HttpResponse::status(599));
+    /// server.json_errors();
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let client = TestClient::new(handle.local_addrs()[0]);
+    /// let teapot = client.get("/teapot").unwrap().text();
+    /// assert_eq!(teapot, r#"{"error":"I'm a teapot","status":418}"#);
+    /// let vendor = client.get("/vendor").unwrap().text();
+    /// assert_eq!(vendor, r#"{"error":"Server Error","status":599}"#);
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// # }
+    /// ```
+    pub fn json_errors(&mut self) {
+        self.error_body(|status_code| {
+            let reason = match HttpResponse::new(status_code, None).get_status_message() {
+                "" if status_code < 500 => "Client Error",
+                "" => "Server Error",
+                reason => reason,
+            };
+            let body = format!(
+                "{{\"error\":{},\"status\":{}}}",
+                response::json_string(reason),
+                status_code
+            );
+            HttpResponse::with_content_type(status_code, "application/json", Some(body))
+        });
+    }
+
+    /// Sets the largest size a compressed request body may expand to.
+    ///
+    /// Request bodies sent with `Content-Encoding: gzip` are decompressed before the handler
+    /// sees them. A body that would decompress to more than `length` bytes is rejected with
+    /// 413 Payload Too Large without being expanded further, which guards against
+    /// decompression bombs. The default is 16 MiB. Requires the `compression` feature.
+    ///
+    /// # Parameters
+    /// - 'length' : The maximum decompressed body length in bytes.
+    #[cfg(feature = "compression")]
+    pub fn max_decompressed_body(&mut self, length: usize) {
+        self.config.max_decompressed_body = length;
+    }
+
+    /// Limits how long a handler may take to produce its response.
+    ///
+    /// With a limit set, each handler runs on a thread of its own and the connection waits for
+    /// it at most `timeout`; a handler that takes longer is abandoned and the client gets 503
+    /// Service Unavailable, so a hanging dependency cannot hold connections open indefinitely.
+    /// A handler that panics gets 500 Internal Server Error.
+    ///
+    /// Rust cannot stop a running thread: an abandoned handler keeps running in the background
+    /// until it returns on its own, and its result is discarded. Any side effects it has,
+    /// including changes to the session, still happen.
+    ///
+    /// # Parameters
+    /// - 'timeout' : The longest time a handler may run.
+    pub fn handler_timeout(&mut self, timeout: Duration) {
+        self.config.handler_timeout = Some(timeout);
+    }
+
+    /// Enables TCP keepalive on accepted connections, so the operating system detects peers
+    /// that vanished without closing the connection.
+    ///
+    /// After `idle` without traffic the OS starts sending probes, and closes the connection if
+    /// the peer stops answering them; a blocked read or write then fails instead of waiting
+    /// forever. Unlike `read_timeout`, this never closes a connection whose peer is still
+    /// there, so it suits connections idle by design, such as long polls or event streams.
+    ///
+    /// # Parameters
+    /// - 'idle' : How long a connection may be idle before the first probe.
+    pub fn tcp_keepalive(&mut self, idle: Duration) {
+        self.config.tcp_keepalive = Some(idle);
+    }
+
+    /// Limits the number of connections open at once, across all listeners.
+    ///
+    /// Every connection holds a thread and a file descriptor; the limit keeps a flood of clients
+    /// from exhausting either. Kept-alive connections count until they close.
+    ///
+    /// # Parameters
+    /// - 'limit' : The most connections served at once.
+    /// - 'at_capacity' : Whether further clients wait to be accepted or are turned away with
+    ///   503 Service Unavailable.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use guest_server::{AtCapacity,HttpResponse,Server};
+    ///
+    /// let mut server = Server::new();
+    /// server.max_connections(512, AtCapacity::Reject);
+    /// server.get("/", |_| HttpResponse::new(200, None));
+    /// server.listener(8080);
+    /// ```
+    pub fn max_connections(&mut self, limit: usize, at_capacity: AtCapacity) {
+        self.config.max_connections = Some((limit, at_capacity));
+    }
+
+    /// Sets how many requests a kept-alive connection may carry before the server closes it.
+    /// The default is 1000.
+    ///
+    /// The last response is sent with `Connection: close`, and the client reconnects for any
+    /// further requests, so that one client sending an endless stream of requests cannot hold
+    /// on to a connection thread forever, and load balancers get to spread clients anew.
+    ///
+    /// # Parameters
+    /// - 'limit' : The most requests per connection, at least 1; `usize::MAX` for no limit.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use guest_server::Server;
+    /// use std::io::{Read, Write};
+    /// use std::net::TcpStream;
+    ///
+    /// let mut server = Server::new();
+    /// server.max_requests_per_connection(1);
+    /// server.get("/", |_| "Hello, World!");
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// // The client asks to keep the connection, but it is closed after the first response
+    /// let mut stream = TcpStream::connect(handle.local_addrs()[0]).unwrap();
+    /// stream.write_all(b"GET / HTTP/1.1\r\nHost: test\r\n\r\n").unwrap();
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response).unwrap();
+    /// assert!(response.contains("\r\nConnection: close\r\n"));
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// ```
+    pub fn max_requests_per_connection(&mut self, limit: usize) {
+        self.config.max_requests = limit.max(1);
+    }
+
+    /// Forwards every request under a path prefix to an upstream server, acting as a reverse
+    /// proxy.
+    ///
+    /// The method, path, query string, headers and body are sent upstream unchanged except for
+    /// hop-by-hop headers such as `Connection` and `Transfer-Encoding`, which are dropped in
+    /// both directions. The client address is appended to `X-Forwarded-For`. The upstream
+    /// response is streamed back as it arrives, or 502 Bad Gateway is sent if the upstream is
+    /// unreachable. Proxies take precedence over routes and are served by `Server::listener`
+    /// and `Server::bind`.
+    ///
+    /// An upstream that does not accept the connection within 10 seconds counts as
+    /// unreachable, as does one that takes more than 30 seconds to accept the request or
+    /// start its response. A response stalling for 30 seconds after it started is cut off.
+    ///
+    /// # Parameters
+    /// - 'path_prefix' : The prefix to forward, e.g. '/api'. It matches whole path segments,
+    ///   so '/api' covers '/api' and '/api/users' but not '/apix'.
+    /// - 'upstream' : The address of the upstream server, e.g. '127.0.0.1:9000' or
+    ///   'http://backend:9000'. Only plain HTTP upstreams are supported.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use guest_server::Server;
+    ///
+    /// let mut server = Server::new();
+    /// server.proxy("/api", "127.0.0.1:9000");
+    /// server.listener(8080);
+    /// ```
+    ///
+    /// Against an upstream recording what reaches it:
+    ///
+    /// ```rust
+    /// use guest_server::Server;
+    /// use std::io::{BufRead, BufReader, Read, Write};
+    /// use std::net::{TcpListener, TcpStream};
+    /// use std::{sync::mpsc, thread};
+    ///
+    /// let upstream = TcpListener::bind("127.0.0.1:0").unwrap();
+    /// let upstream_addr = upstream.local_addr().unwrap().to_string();
+    /// let (sender, received) = mpsc::channel();
+    /// thread::spawn(move || {
+    ///     let (connection, _) = upstream.accept().unwrap();
+    ///     let mut reader = BufReader::new(connection.try_clone().unwrap());
+    ///     let mut head = String::new();
+    ///     while !head.ends_with("\r\n\r\n") {
+    ///         reader.read_line(&mut head).unwrap();
+    ///     }
+    ///     sender.send(head).unwrap();
+    ///     let mut connection = connection;
+    ///     let response = "HTTP/1.1 200 OK\r\nKeep-Alive: timeout=5\r\n\
+    ///                     X-Upstream: 1\r\n\r\nhello";
+    ///     connection.write_all(response.as_bytes()).unwrap();
+    /// });
+    ///
+    /// let mut server = Server::new();
+    /// server.proxy("/api", &upstream_addr);
+    /// let down = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    /// server.proxy("/down", &down.to_string());
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let send = |request: &str| {
+    ///     let mut stream = TcpStream::connect(handle.local_addrs()[0]).unwrap();
+    ///     stream.write_all(request.as_bytes()).unwrap();
+    ///     let mut response = String::new();
+    ///     stream.read_to_string(&mut response).unwrap();
+    ///     response
+    /// };
+    /// let response = send(
+    ///     "GET /api/users?page=2 HTTP/1.1\r\nHost: test\r\nX-Custom: yes\r\n\
+    ///      X-Forwarded-For: 10.0.0.1\r\nConnection: close, X-Hop\r\nX-Hop: 1\r\n\
+    ///      Keep-Alive: 300\r\nTE: trailers\r\n\r\n",
+    /// );
+    /// let head = received.recv().unwrap();
+    /// assert!(head.starts_with("GET /api/users?page=2 HTTP/1.0\r\n"));
+    /// assert!(head.contains("x-custom: yes\r\n"));
+    /// assert!(head.contains("X-Forwarded-For: 10.0.0.1, 127.0.0.1\r\n"));
+    /// for hop_by_hop in ["connection", "x-hop", "keep-alive", "te:"] {
+    ///     assert!(!head.to_lowercase().contains(hop_by_hop), "{}", hop_by_hop);
+    /// }
+    /// assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    /// assert!(response.contains("X-Upstream: 1\r\n"));
+    /// assert!(!response.contains("Keep-Alive"));
+    /// assert!(response.ends_with("\r\n\r\nhello"));
+    ///
+    /// let response = send("GET /down HTTP/1.1\r\nHost: test\r\n\r\n");
+    /// assert!(response.starts_with("HTTP/1.1 502 Bad Gateway\r\n"));
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// ```
+    pub fn proxy(&mut self, path_prefix: &str, upstream: &str) {
+        let upstream = upstream.strip_prefix("http://").unwrap_or(upstream);
+        self.config.proxies.push(proxy::Proxy {
+            prefix: path_prefix.to_string(),
+            upstream: upstream.trim_end_matches('/').to_string(),
+        });
+    }
+
+    /// Accepts CONNECT requests, acting as a forward proxy that tunnels connections to the
+    /// targets `allow` approves.
+    ///
+    /// Without this an origin server has no business accepting CONNECT, so it is refused with
+    /// 405 Method Not Allowed. With it, a CONNECT request for an approved `host:port` gets
+    /// `200 Connection Established` and the connection is relayed to the target byte for byte
+    /// until either side closes, typically carrying TLS. Targets `allow` rejects get 403
+    /// Forbidden. Tunnels are served by `Server::listener` and `Server::bind`.
+    ///
+    /// Approve targets narrowly: a server tunneling anywhere is an open proxy, which lets
+    /// anyone reach hosts only the server can see and hide their traffic behind it.
+    ///
+    /// # Parameters
+    /// - 'allow' : Decides whether a target, such as `example.com:443`, may be tunneled to.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use guest_server::Server;
+    ///
+    /// let mut server = Server::new();
+    /// server.tunnel(|target| target == "internal.example.com:443");
+    /// server.listener(8080);
+    /// ```
+    ///
+    /// The target is taken as sent, even with `normalize_paths` and `path_rewrite` set:
+    ///
+    /// ```rust
+    /// use guest_server::Server;
+    /// use std::borrow::Cow;
+    /// use std::io::{Read, Write};
+    /// use std::net::{TcpListener, TcpStream};
+    /// use std::thread;
+    ///
+    /// // A target echoing back what it receives
+    /// let target = TcpListener::bind("127.0.0.1:0").unwrap();
+    /// let authority = target.local_addr().unwrap().to_string();
+    /// thread::spawn(move || {
+    ///     let (mut connection, _) = target.accept().unwrap();
+    ///     let mut reader = connection.try_clone().unwrap();
+    ///     std::io::copy(&mut reader, &mut connection).unwrap();
+    /// });
+    ///
+    /// let mut server = Server::new();
+    /// let allowed = authority.clone();
+    /// server.tunnel(move |target| target == allowed);
+    /// server.normalize_paths(true);
+    /// server.path_rewrite(|path| Cow::Owned(format!("/app{}", path)));
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let mut stream = TcpStream::connect(handle.local_addrs()[0]).unwrap();
+    /// let request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n\r\nping", authority);
+    /// stream.write_all(request.as_bytes()).unwrap();
+    /// let mut response = [0; 43];
+    /// stream.read_exact(&mut response).unwrap();
+    /// assert_eq!(&response[..], b"HTTP/1.1 200 Connection Established\r\n\r\nping");
+    ///
+    /// let mut stream = TcpStream::connect(handle.local_addrs()[0]).unwrap();
+    /// let request = "CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\n";
+    /// stream.write_all(request.as_bytes()).unwrap();
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response).unwrap();
+    /// assert!(response.starts_with("HTTP/1.1 403 Forbidden\r\n"));
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// ```
+    pub fn tunnel<F>(&mut self, allow: F)
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.config.tunnel = Some(Arc::new(allow));
+    }
+
+    /// Enables an access log written to stdout, one line per request.
+    ///
+    /// # Parameters
+    /// - 'format' : The line format, `LogFormat::Common` or `LogFormat::Combined`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use guest_server::{LogFormat,Server};
+    ///
+    /// let mut server = Server::new();
+    /// server.access_log(LogFormat::Combined);
+    /// server.listener(8080);
+    /// ```
+    pub fn access_log(&mut self, format: LogFormat) {
+        self.access_log_to(format, std::io::stdout());
+    }
+
+    /// Enables an access log written to the given writer, one line per request.
+    ///
+    /// Lines are written as each response is sent, so wrap slow writers such as files in a
+    /// `BufWriter` if throughput matters more than seeing every line immediately.
+    ///
+    /// # Parameters
+    /// - 'format' : The line format, `LogFormat::Common` or `LogFormat::Combined`.
+    /// - 'writer' : Where to write the lines, e.g. a `File`.
+    pub fn access_log_to<W: Write + Send + 'static>(&mut self, format: LogFormat, writer: W) {
+        self.config.access_log = Some(access_log::AccessLog {
+            format,
+            writer: Arc::new(Mutex::new(writer)),
+        });
+    }
+
+    /// Returns the number of connections currently being handled, across all listeners of
+    /// this server.
+    pub fn active_connections(&self) -> usize {
+        self.config.active_connections.load(Ordering::SeqCst)
+    }
+
+    /// Returns the counts of connections and requests handled, across all listeners of this
+    /// server. See `Counters`.
+    pub fn counters(&self) -> Arc<Counters> {
+        Arc::clone(&self.config.counters)
+    }
+
+    /// Adds or updates multiple default response headers in batch.
+    ///
+    /// # Parameters
+    /// - 'headers' : A HashMap containing the default header fields.
+    pub fn default_headers(&mut self, headers: HashMap<String, String>) {
+        self.config.default_headers.extend(headers);
+    }
+
+    /// Registers a route with a specific HTTP method, path, and handler.
+    ///
+    /// # Parameters
+    /// - 'method' : The HTTP method (GET, POST, PATCH) for this route.
+    /// - 'path' : The route path (e.g., '/home').
+    /// - 'handler' : The closure that processes the request for this path, returning anything
+    ///   that implements `IntoResponse`.
+    /// - 'timeout' : How long the handler may run, overriding the server-wide
+    ///   `handler_timeout`; `None` to use it.
+    fn route<F, T>(&mut self, method: HttpMethod, path: &str, handler: F, timeout: Option<Duration>)
+    where
+        F: Fn(&Request) -> T + Send + Sync + 'static,
+        T: IntoResponse,
+    {
+        let route = Route {
+            handler: Arc::new(move |request| handler(request).into_response()),
+            timeout,
+            pattern: Some(path.to_string()),
+        };
+        match &mut self.routes {
+            Routes::Shared(router) => router.write().unwrap_or_else(PoisonError::into_inner).add(
+                method.clone(),
+                path,
+                route,
+            ),
+            // Only a frozen router that is not yet serving can still be changed
+            Routes::Frozen(router) => match Arc::get_mut(router) {
+                Some(router) => router.add(method.clone(), path, route),
+                None => {
+                    log_error!("Route {} not added: the routes are frozen", path);
+                    return;
+                }
+            },
+        }
+        self.route_table.add(method, path);
+    }
+
+    /// Fixes the routes, returning the server to serve them without taking a lock.
+    ///
+    /// Until frozen, the router sits behind a read-write lock, so that routes can be added
+    /// even while the server is serving; every request then takes the lock to look up its
+    /// route. Once the routes are complete, freezing removes that cost. Routes added after
+    /// freezing still count if the server is not serving yet, and are ignored, with an error
+    /// logged, once it is. Freezing a server that is already serving has no effect, as its
+    /// connections keep using the shared router.
+    ///
+    /// # Returns
+    /// The server with the same routes and settings.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use guest_server::Server;
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/", |_| "Hello, World!");
+    /// server.get("/users/:id", |request| format!("User {}", request.params["id"]));
+    /// let server = server.freeze();
+    /// server.listener(8080);
+    /// ```
+    pub fn freeze(mut self) -> Self {
+        if let Routes::Shared(router) = self.routes {
+            // Connections already holding the shared router would not see a frozen copy
+            self.routes = match Arc::try_unwrap(router) {
+                Ok(router) => {
+                    let router = router.into_inner().unwrap_or_else(PoisonError::into_inner);
+                    Routes::Frozen(Arc::new(router))
+                }
+                Err(router) => {
+                    log_warn!("Routes not frozen: the server is already serving");
+                    Routes::Shared(router)
+                }
+            };
+        }
+        self
+    }
+
+    /// Returns the routes registered so far, e.g. to print them at startup. See
+    /// `RouteTable`.
+    pub fn routes(&self) -> &RouteTable {
+        &self.route_table
+    }
+
+    /// Lists the methods a path can be requested with, as sent in the `Allow` header of 405
+    /// and OPTIONS responses.
+    ///
+    /// The methods of every route matching the path are included, along with GET for paths
+    /// under a static directory, HEAD whenever GET is, and OPTIONS always. Each appears once,
+    /// in the order GET, HEAD, POST, PATCH, OPTIONS, whatever order the routes were
+    /// registered in and whatever the router reports.
+    ///
+    /// # Parameters
+    /// - 'path' : The request path, or `*` for the server as a whole.
+    ///
+    /// # Returns
+    /// The methods, or an empty list if no route matches the path.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use guest_server::{HttpMethod, Server};
+    ///
+    /// let mut server = Server::new();
+    /// server.patch("/users/:id", |_| "updated");
+    /// server.get("/users/:id", |_| "user");
+    ///
+    /// assert_eq!(
+    ///     server.allowed_methods_for("/users/42"),
+    ///     [HttpMethod::GET, HttpMethod::HEAD, HttpMethod::PATCH, HttpMethod::OPTIONS]
+    /// );
+    /// assert!(server.allowed_methods_for("/missing").is_empty());
+    /// ```
+    pub fn allowed_methods_for(&self, path: &str) -> Vec<HttpMethod> {
+        Server::allowed_methods(&self.routes, &self.config, path)
+    }
+
+    /// Registers a GET route with a specified path and handler.
+    ///
+    /// # Parameters
+    /// - 'path' : The route path to register, e.g., '/home'.
+    /// - 'handler' : The closure that processes the request for this path.
+    pub fn get<F, T>(&mut self, path: &str, handler: F)
+    where
+        F: Fn(&Request) -> T + Send + Sync + 'static,
+        T: IntoResponse,
+    {
+        self.route(HttpMethod::GET, path, handler, None);
+    }
+
+    /// Registers a POST route with a specified path and handler.
+    ///
+    /// # Parameters
+    /// - 'path' : The route path to register, e.g., '/submit'.
+    /// - 'handler' : The closure that processes the request for this path.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use guest_server::{Server,HttpResponse,Request};
+    /// let mut server = Server::new();
+    /// server.post("/submit",submit);
+    /// fn submit(request: &Request) -> HttpResponse {
+    ///     HttpResponse::with_content_type(200, "application/json", Some("{\"key\":\"value\"}".to_string()))
+    /// }
+    /// server.listener(8080);
+    /// ```
+    pub fn post<F, T>(&mut self, path: &str, handler: F)
+    where
+        F: Fn(&Request) -> T + Send + Sync + 'static,
+        T: IntoResponse,
+    {
+        self.route(HttpMethod::POST, path, handler, None);
+    }
+
+    /// Registers a PATCH route with a specified path and handler.
+    ///
+    /// # Parameters
+    /// - 'path' : The route path to register, e.g., '/users/1'.
+    /// - 'handler' : The closure that processes the request for this path.
+    pub fn patch<F, T>(&mut self, path: &str, handler: F)
+    where
+        F: Fn(&Request) -> T + Send + Sync + 'static,
+        T: IntoResponse,
+    {
+        self.route(HttpMethod::PATCH, path, handler, None);
+    }
+
+    /// Registers a GET route whose handler may run for `timeout`, overriding the server-wide
+    /// `handler_timeout` for this route alone.
+    ///
+    /// The handler runs on a thread of its own, as described for `handler_timeout`, even when
+    /// no server-wide limit is set.
+    ///
+    /// # Parameters
+    /// - 'path' : The route path to register, e.g., '/reports/yearly'.
+    /// - 'handler' : The closure that processes the request for this path.
+    /// - 'timeout' : The longest time the handler may run.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use guest_server::{HttpResponse,Server};
+    /// use std::time::Duration;
+    ///
+    /// let mut server = Server::new();
+    /// server.handler_timeout(Duration::from_secs(5));
+    /// server.get_with_timeout("/reports/yearly", |_| HttpResponse::new(200, None), Duration::from_secs(120));
+    /// server.listener(8080);
+    /// ```
+    pub fn get_with_timeout<F, T>(&mut self, path: &str, handler: F, timeout: Duration)
+    where
+        F: Fn(&Request) -> T + Send + Sync + 'static,
+        T: IntoResponse,
+    {
+        self.route(HttpMethod::GET, path, handler, Some(timeout));
+    }
+
+    /// Registers a POST route whose handler may run for `timeout`, as `get_with_timeout` does
+    /// for GET.
+    pub fn post_with_timeout<F, T>(&mut self, path: &str, handler: F, timeout: Duration)
+    where
+        F: Fn(&Request) -> T + Send + Sync + 'static,
+        T: IntoResponse,
+    {
+        self.route(HttpMethod::POST, path, handler, Some(timeout));
+    }
+
+    /// Registers a PATCH route whose handler may run for `timeout`, as `get_with_timeout` does
+    /// for GET.
+    pub fn patch_with_timeout<F, T>(&mut self, path: &str, handler: F, timeout: Duration)
+    where
+        F: Fn(&Request) -> T + Send + Sync + 'static,
+        T: IntoResponse,
+    {
+        self.route(HttpMethod::PATCH, path, handler, Some(timeout));
+    }
+
+    /// Registers a POST route whose request body is streamed into a writer rather than
+    /// buffered in `Request::body`, e.g. for large uploads written straight to disk.
+    ///
+    /// Once the headers are read, `sink` opens the writer, and the body is copied into it as
+    /// it arrives. Then `handler` gets the request, with an empty `body`, and the writer back,
+    /// e.g. to sync or rename the file. A body larger than `limit` is refused with 413 before
+    /// `sink` is called. A body that ends early gets 400 and a failing writer 500, without
+    /// the handler running. Bodies are passed on as sent, even if `Content-Encoding` is set.
+    ///
+    /// A body left unread, because it was refused or `sink` returned a response, is read and
+    /// discarded if at most 64 KiB of it remain, so the connection can carry on with the next
+    /// request. Otherwise the response is sent right away with `Connection: close`, and the
+    /// connection closed once the client stops sending, or after a short grace period, so
+    /// the client still gets the response rather than a reset.
+    ///
+    /// The path is matched exactly, without parameters, and takes precedence over a POST
+    /// route of the same path. The event-loop server buffers the body first, then copies it
+    /// into the writer the same way.
+    ///
+    /// # Parameters
+    /// - 'path' : The exact route path, e.g. '/upload'.
+    /// - 'limit' : The largest body accepted, in bytes.
+    /// - 'sink' : The closure opening the writer for a request, or refusing it with a
+    ///   response.
+    /// - 'handler' : The closure building the response once the body is written.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use guest_server::{HttpResponse,Server};
+    /// use std::fs::File;
+    ///
+    /// let mut server = Server::new();
+    /// server.post_streaming(
+    ///     "/upload",
+    ///     1 << 30,
+    ///     |_| File::create("upload.bin").map_err(|_| HttpResponse::new(500, None)),
+    ///     |_, file: File| match file.sync_all() {
+    ///         Ok(()) => HttpResponse::new(201, None),
+    ///         Err(_) => HttpResponse::new(500, None),
+    ///     },
+    /// );
+    /// server.listener(8080);
+    /// ```
+    ///
+    /// A refused body is skipped, so a kept-alive connection stays usable:
+    ///
+    /// ```rust
+    /// use guest_server::Server;
+    /// use std::io::{Read, Write};
+    /// use std::net::TcpStream;
+    ///
+    /// let mut server = Server::new();
+    /// server.post_streaming("/upload", 4, |_| Ok(Vec::new()), |_, _| "stored");
+    /// server.get("/", |_| "next");
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let mut stream = TcpStream::connect(handle.local_addrs()[0]).unwrap();
+    /// let requests = "POST /upload HTTP/1.1\r\nHost: test\r\nContent-Length: 10\r\n\r\n0123456789\
+    ///                 GET / HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n";
+    /// stream.write_all(requests.as_bytes()).unwrap();
+    /// let mut responses = String::new();
+    /// stream.read_to_string(&mut responses).unwrap();
+    /// assert!(responses.starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+    /// assert!(responses.contains("HTTP/1.1 200 OK\r\n"));
+    /// assert!(responses.ends_with("\r\n\r\nnext"));
+    ///
+    /// // Too much to skip: answered without reading on, then the connection is closed
+    /// let mut stream = TcpStream::connect(handle.local_addrs()[0]).unwrap();
+    /// let head = "POST /upload HTTP/1.1\r\nHost: test\r\nContent-Length: 262144\r\n\r\n";
+    /// stream.write_all(head.as_bytes()).unwrap();
+    /// stream.write_all(&[b'x'; 262144]).unwrap();
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response).unwrap();
+    /// assert!(response.starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+    /// assert!(response.contains("\r\nConnection: close\r\n"));
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// ```
+    ///
+    /// The body is delimited by `Content-Length` as for any other route:
+    ///
+    /// ```rust
+    /// use guest_server::Server;
+    /// use std::io::{Read, Write};
+    /// use std::net::{Shutdown, TcpStream};
+    ///
+    /// let mut server = Server::new();
+    /// server.post_streaming("/upload", 8, |_| Ok(Vec::new()), |_, body: Vec<u8>| {
+    ///     format!("stored {} bytes", body.len())
+    /// });
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let send = |headers: &str, body: &str| {
+    ///     let mut stream = TcpStream::connect(handle.local_addrs()[0]).unwrap();
+    ///     let head = "POST /upload HTTP/1.1\r\nHost: test\r\nConnection: close\r\n";
+    ///     let request = format!("{}{}\r\n{}", head, headers, body);
+    ///     stream.write_all(request.as_bytes()).unwrap();
+    ///     // The client sends nothing more, however much the length promised
+    ///     stream.shutdown(Shutdown::Write).unwrap();
+    ///     let mut response = String::new();
+    ///     stream.read_to_string(&mut response).unwrap();
+    ///     response
+    /// };
+    /// assert!(send("Content-Length: 5\r\n", "12345").ends_with("\r\n\r\nstored 5 bytes"));
+    /// assert!(send("", "").ends_with("\r\n\r\nstored 0 bytes"));
+    /// assert!(send("Content-Length: 9\r\n", "123456789").starts_with("HTTP/1.1 413 "));
+    /// assert!(send("Content-Length: 5\r\n", "123").starts_with("HTTP/1.1 400 "));
+    /// for invalid in ["Content-Length: five\r\n", "Content-Length: -5\r\n",
+    ///                 "Content-Length: 5\r\nContent-Length: 3\r\n"] {
+    ///     assert!(send(invalid, "12345").starts_with("HTTP/1.1 400 "), "{}", invalid);
+    /// }
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// ```
+    pub fn post_streaming<W, S, F, T>(&mut self, path: &str, limit: u64, sink: S, handler: F)
+    where
+        W: Write,
+        S: Fn(&Request) -> Result<W, HttpResponse> + Send + Sync + 'static,
+        F: Fn(&Request, W) -> T + Send + Sync + 'static,
+        T: IntoResponse,
+    {
+        let upload = upload::Upload::new(HttpMethod::POST, path, limit, sink, handler);
+        self.add_upload(path, upload);
+    }
+
+    /// Registers a POST route whose handler reads the request body on demand, rather than
+    /// getting it buffered in `Request::body`.
+    ///
+    /// The handler runs once the headers are read, with a `BodyReader` positioned at the
+    /// start of the body, and can ignore it, stream it, or read it whole, e.g. depending on a
+    /// header. Whatever it leaves unread is discarded like for `post_streaming`: up to 64 KiB
+    /// of it is read so the connection can carry on with the next request, otherwise the
+    /// connection is closed after the response.
+    ///
+    /// The path is matched exactly, without parameters, and takes precedence over a POST
+    /// route of the same path, as for `post_streaming`.
+    ///
+    /// # Parameters
+    /// - 'path' : The exact route path, e.g. '/events'.
+    /// - 'handler' : The closure processing the request and reading its body.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use guest_server::{BodyReader, Server};
+    /// use std::io::{Read, Write};
+    /// use std::net::TcpStream;
+    ///
+    /// let mut server = Server::new();
+    /// server.post_lazy("/events", |request, mut body: BodyReader| {
+    ///     if request.headers.contains_key("x-dry-run") {
+    ///         return "skipped".to_string();
+    ///     }
+    ///     let mut events = String::new();
+    ///     match body.read_to_string(&mut events) {
+    ///         Ok(_) => format!("stored {}", events.lines().count()),
+    ///         Err(_) => "unreadable".to_string(),
+    ///     }
+    /// });
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let mut stream = TcpStream::connect(handle.local_addrs()[0]).unwrap();
+    /// let requests = "POST /events HTTP/1.1\r\nHost: test\r\nX-Dry-Run: 1\r\n\
+    ///                 Content-Length: 4\r\n\r\na\nb\n\
+    ///                 POST /events HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\
+    ///                 Content-Length: 6\r\n\r\na\nb\nc\n";
+    /// stream.write_all(requests.as_bytes()).unwrap();
+    /// let mut responses = String::new();
+    /// stream.read_to_string(&mut responses).unwrap();
+    /// assert!(responses.contains("\r\n\r\nskippedHTTP/1.1 200 OK\r\n"));
+    /// assert!(responses.ends_with("\r\n\r\nstored 3"));
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// ```
+    pub fn post_lazy<F, T>(&mut self, path: &str, handler: F)
+    where
+        F: Fn(&Request, BodyReader) -> T + Send + Sync + 'static,
+        T: IntoResponse,
+    {
+        let upload = upload::Upload::lazy(HttpMethod::POST, path, handler);
+        self.add_upload(path, upload);
+    }
+
+    /// Registers a POST route whose request body is kept in memory when small, and spooled
+    /// to a temporary file when large, e.g. for an endpoint taking both JSON documents and
+    /// file uploads.
+    ///
+    /// A body of at most `threshold` bytes reaches the handler as `SpooledBody::Memory`. A
+    /// larger one is written to a file in the system's temporary directory as it arrives,
+    /// and reaches the handler as `SpooledBody::File`; the file is removed once the handler
+    /// returns. `Request::body` is left empty either way. A body larger than `limit` is
+    /// refused with 413, a body that ends early gets 400, and a failure to write the file
+    /// 500, without the handler running.
+    ///
+    /// The path is matched exactly, without parameters, and takes precedence over a POST
+    /// route of the same path, as for `post_streaming`.
+    ///
+    /// # Parameters
+    /// - 'path' : The exact route path, e.g. '/documents'.
+    /// - 'threshold' : The largest body kept in memory, in bytes.
+    /// - 'limit' : The largest body accepted, in bytes.
+    /// - 'handler' : The closure building the response from the request and its body.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "testing")] {
+    /// use guest_server::{Server, SpooledBody, TestClient};
+    /// use std::io::Read;
+    ///
+    /// let mut server = Server::new();
+    /// server.post_spooled("/documents", 1024, 1 << 30, |_, body| {
+    ///     let held = match &body {
+    ///         SpooledBody::Memory(_) => "memory",
+    ///         SpooledBody::File(_) => "file",
+    ///     };
+    ///     let mut length = 0;
+    ///     let mut reader = body.into_reader();
+    ///     let mut buffer = [0; 4096];
+    ///     while let Ok(read @ 1..) = reader.read(&mut buffer) {
+    ///         length += read;
+    ///     }
+    ///     format!("{} bytes in {}", length, held)
+    /// });
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let client = TestClient::new(handle.local_addrs()[0]);
+    /// let small = client.post("/documents", b"{\"title\": \"notes\"}").unwrap();
+    /// assert_eq!(small.text(), "18 bytes in memory");
+    /// let large = client.post("/documents", &[0; 100_000]).unwrap();
+    /// assert_eq!(large.text(), "100000 bytes in file");
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// # }
+    /// ```
+    pub fn post_spooled<F, T>(&mut self, path: &str, threshold: usize, limit: u64, handler: F)
+    where
+        F: Fn(&Request, SpooledBody) -> T + Send + Sync + 'static,
+        T: IntoResponse,
+    {
+        let upload =
+            upload::Upload::spooled(HttpMethod::POST, path, threshold as u64, limit, handler);
+        self.add_upload(path, upload);
+    }
+
+    /// Registers a route that reads its own body, replacing one already registered for the
+    /// path.
+    fn add_upload(&mut self, path: &str, upload: upload::Upload) {
+        self.route_table.add(HttpMethod::POST, path);
+        self.config.uploads.retain(|existing| existing.path != path);
+        self.config.uploads.push(upload);
+    }
+
+    /// Registers a GET route serving a single file, e.g. `/favicon.ico` or `/robots.txt`.
+    ///
+    /// The file is read at request time, so it may change or appear while the server runs;
+    /// if it does not exist then, the request gets 404. The `Content-Type` is guessed from the
+    /// file extension, and `Last-Modified` is sent so browsers can revalidate their copy with
+    /// `If-Modified-Since` and get 304 Not Modified while it is unchanged.
+    ///
+    /// # Parameters
+    /// - 'path' : The route path, e.g. '/favicon.ico'.
+    /// - 'file' : The file to serve, relative to the working directory or absolute.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use guest_server::Server;
+    ///
+    /// let mut server = Server::new();
+    /// server.static_file("/favicon.ico", "assets/favicon.ico");
+    /// server.static_file("/robots.txt", "assets/robots.txt");
+    /// server.listener(8080);
+    /// ```
+    ///
+    /// A file compressed at build time next to the original is sent to clients accepting it:
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "testing")] {
+    /// use guest_server::{Server, TestClient};
+    /// use std::fs;
+    ///
+    /// let dir = std::env::temp_dir().join(format!("guest-static-file-{}", std::process::id()));
+    /// fs::create_dir_all(&dir).unwrap();
+    /// let file = dir.join("site.css");
+    /// fs::write(&file, "body { color: black }").unwrap();
+    /// fs::write(dir.join("site.css.gz"), b"\x1f\x8bcompressed").unwrap();
+    ///
+    /// let mut server = Server::new();
+    /// server.static_file("/site.css", file.to_str().unwrap());
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let client = TestClient::new(handle.local_addrs()[0]);
+    /// let gzip = [("Accept-Encoding", "gzip, deflate")];
+    /// let response = client.request("GET", "/site.css", &gzip, b"").unwrap();
+    /// assert_eq!(response.body, b"\x1f\x8bcompressed");
+    /// assert_eq!(response.headers["content-encoding"], "gzip");
+    /// assert_eq!(response.headers["content-type"], "text/css; charset=utf-8");
+    /// assert_eq!(response.headers["content-length"], "12");
+    /// assert_eq!(response.headers["vary"], "Accept-Encoding");
+    ///
+    /// let response = client.get("/site.css").unwrap();
+    /// assert_eq!(response.text(), "body { color: black }");
+    /// assert!(!response.headers.contains_key("content-encoding"));
+    /// assert_eq!(response.headers["vary"], "Accept-Encoding");
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// fs::remove_dir_all(&dir).unwrap();
+    /// # }
+    /// ```
+    pub fn static_file(&mut self, path: &str, file: &str) {
+        self.static_file_route(path, file, None);
+    }
+
+    /// Registers a GET route serving a single file with a `Cache-Control` header, which is
+    /// also sent with 304 responses. See `static_file` and `CacheControl`.
+    ///
+    /// # Parameters
+    /// - 'path' : The route path, e.g. '/favicon.ico'.
+    /// - 'file' : The file to serve, relative to the working directory or absolute.
+    /// - 'cache_control' : How long browsers may reuse the file.
+    pub fn static_file_with_cache(&mut self, path: &str, file: &str, cache_control: CacheControl) {
+        self.static_file_route(path, file, Some(cache_control));
+    }
+
+    /// Registers the GET route behind `static_file` and `static_file_with_cache`.
+    fn static_file_route(&mut self, path: &str, file: &str, cache_control: Option<CacheControl>) {
+        let file = PathBuf::from(file);
+        self.get(path, move |request| {
+            static_files::serve_file(&file, None, request, cache_control.as_ref())
+        });
+    }
+
+    /// Serves the files in a directory under a path prefix, for GET and HEAD requests.
+    ///
+    /// Files are served as with `static_file`. Routes take precedence: a request under the
+    /// prefix that a route matches goes to the route. See `StaticDir` for how paths map to
+    /// files and for the options.
+    ///
+    /// # Parameters
+    /// - 'prefix' : The path prefix, e.g. '/assets'. It matches whole path segments, so
+    ///   '/assets' covers '/assets/site.css' but not '/assetsx'.
+    /// - 'dir' : The directory and its options.
+    ///
+    /// # Example
+    ///
+    /// Precompressed variants are chosen by the q-values of `Accept-Encoding`:
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "testing")] {
+    /// use guest_server::{Server, StaticDir, TestClient};
+    /// use std::fs;
+    ///
+    /// let dir = std::env::temp_dir().join(format!("guest-static-dir-{}", std::process::id()));
+    /// fs::create_dir_all(&dir).unwrap();
+    /// fs::write(dir.join("app.js"), "plain").unwrap();
+    /// fs::write(dir.join("app.js.br"), "brotli").unwrap();
+    /// fs::write(dir.join("app.js.gz"), "gzip").unwrap();
+    /// fs::write(dir.join("notes.txt"), "notes").unwrap();
+    ///
+    /// let mut server = Server::new();
+    /// server.static_dir("/assets", StaticDir::new(dir.to_str().unwrap()));
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let client = TestClient::new(handle.local_addrs()[0]);
+    /// let get = |accept: &str| {
+    ///     let headers = [("Accept-Encoding", accept)];
+    ///     client.request("GET", "/assets/app.js", &headers, b"").unwrap()
+    /// };
+    /// // Equal q-values prefer br, then gzip
+    /// let response = get("gzip, br");
+    /// assert_eq!(response.text(), "brotli");
+    /// assert_eq!(response.headers["content-encoding"], "br");
+    /// assert_eq!(response.headers["vary"], "Accept-Encoding");
+    /// assert_eq!(get("br;q=0, gzip").text(), "gzip");
+    /// assert_eq!(get("br;q=0.5, gzip;q=0.8, identity;q=0.1").text(), "gzip");
+    /// // The original, unless listed, is as acceptable as anything
+    /// assert_eq!(get("br;q=0.5, gzip;q=0.8").text(), "plain");
+    /// assert_eq!(get("identity;q=0, gzip").text(), "gzip");
+    /// let response = get("identity");
+    /// assert_eq!(response.text(), "plain");
+    /// assert!(!response.headers.contains_key("content-encoding"));
+    /// // Refusing every coding, the original included, leaves nothing to send
+    /// for refused in ["*;q=0", "identity;q=0", "br;q=0, gzip;q=0, identity;q=0"] {
+    ///     let response = get(refused);
+    ///     assert_eq!(response.status_code, 406);
+    ///     assert_eq!(response.headers["vary"], "Accept-Encoding");
+    /// }
+    /// // A file without variants does not depend on the header
+    /// let response = client.get("/assets/notes.txt").unwrap();
+    /// assert!(!response.headers.contains_key("vary"));
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// fs::remove_dir_all(&dir).unwrap();
+    /// # }
+    /// ```
+    pub fn static_dir(&mut self, prefix: &str, dir: StaticDir) {
+        let mut dir = dir;
+        dir.prefix = prefix.to_string();
+        self.config.static_dirs.push(dir);
+    }
+
+    /// Starts the server and listens for incoming connections on the specified port.
+    ///
+    /// # Parameters
+    /// - 'port' : The port number to listen on.
+    pub fn listener(&self, port: u16) {
+        let addr: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+        let listener = TcpListener::bind(addr).unwrap();
+        let config = Arc::new(self.config.clone());
+        Server::accept_loop(
+            listener,
+            self.routes.clone(),
+            config,
+            Arc::new(AtomicBool::new(false)),
+        );
+    }
+
+    /// Binds every given address and serves them all in the background, sharing the routes.
+    ///
+    /// Each address gets its own accept loop thread. The returned handle stops all of them at
+    /// once with `ServerHandle::shutdown`.
+    ///
+    /// # Parameters
+    /// - 'addrs' : The addresses to listen on, e.g. a slice of `SocketAddr`. A host name that
+    ///   resolves to several addresses binds each of them. IPv6 addresses such as `[::1]:8080`
+    ///   are bound as given; see `dual_stack` for serving IPv4 on them too.
+    ///
+    /// # Returns
+    /// A handle to the running server, or the error of the first address that failed to bind.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use guest_server::{Server,HttpResponse};
+    /// use std::net::SocketAddr;
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/", |_| HttpResponse::new(200, Some("Hello, World!".to_string())));
+    ///
+    /// let addrs: [SocketAddr; 2] = ["127.0.0.1:80".parse().unwrap(), "127.0.0.1:8080".parse().unwrap()];
+    /// let handle = server.bind(&addrs[..]).unwrap();
+    /// // ...
+    /// handle.shutdown();
+    /// handle.join();
+    /// ```
+    pub fn bind<A: ToSocketAddrs>(&self, addrs: A) -> Result<ServerHandle, Error> {
+        let listeners = addrs
+            .to_socket_addrs()?
+            .map(|addr| Server::bind_listener(addr, &self.config))
+            .collect::<Result<Vec<_>, _>>()?;
+        if listeners.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "No address to bind"));
+        }
+
+        let config = Arc::new(self.config.clone());
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let mut addrs = Vec::new();
+        let mut threads = Vec::new();
+        for listener in listeners {
+            addrs.push(listener.local_addr()?);
+            let routes = self.routes.clone();
+            let config = Arc::clone(&config);
+            let shutdown = Arc::clone(&shutdown);
+            threads.push(thread::spawn(move || {
+                Server::accept_loop(listener, routes, config, shutdown)
+            }));
+        }
+
+        Ok(ServerHandle {
+            addrs,
+            shutdown,
+            threads,
+            active_connections: Arc::clone(&self.config.active_connections),
+            counters: Arc::clone(&self.config.counters),
+        })
+    }
+
+    /// Binds every given address and serves them until a shutdown signal fires, then returns
+    /// once the requests in progress have been answered.
+    ///
+    /// This is `bind`, `ServerHandle::shutdown` and waiting for in-flight requests in one
+    /// call, for programs that just run the server until told to stop. A request is waited
+    /// for from its first byte until its response, streamed bodies included, has been
+    /// written. Connections kept alive by idle clients are not waited for: they are closed
+    /// after their next request, or by `read_timeout`. Use `bind` and `ServerHandle::drain`
+    /// to bound the wait instead.
+    ///
+    /// # Parameters
+    /// - 'addrs' : The addresses to listen on, as for `bind`.
+    /// - 'signal' : Fires to stop the server: a `Receiver<()>` when it receives a message or
+    ///   its sender is dropped, or an `Arc<AtomicBool>` once it is set to `true`.
+    ///
+    /// # Returns
+    /// `Ok` once the server has stopped, or the error of the first address that failed to
+    /// bind.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use guest_server::Server;
+    /// use std::sync::mpsc;
+    /// use std::thread;
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/", |_| "Hello, World!");
+    ///
+    /// let (stop, stopped) = mpsc::channel();
+    /// let serving = thread::spawn(move || server.serve_until("127.0.0.1:8080", stopped));
+    /// // ...
+    /// stop.send(()).unwrap();
+    /// serving.join().unwrap().unwrap();
+    /// ```
+    pub fn serve_until<A, S>(&self, addrs: A, signal: S) -> Result<(), Error>
+    where
+        A: ToSocketAddrs,
+        S: ShutdownSignal,
+    {
+        let handle = self.bind(addrs)?;
+        signal.wait();
+        handle.shutdown();
+        handle.join();
+        while self.config.answering.load(Ordering::SeqCst) > 0 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        Ok(())
+    }
+}
+
+/// Something that tells `Server::serve_until` when to stop.
+///
+/// Implemented for `Receiver<()>`, which fires when it receives a message or its sender is
+/// dropped, and for `Arc<AtomicBool>`, which fires once set to `true`.
+pub trait ShutdownSignal {
+    /// Blocks until the signal fires.
+    fn wait(self);
+}
+
+impl ShutdownSignal for Receiver<()> {
+    fn wait(self) {
+        let _ = self.recv();
+    }
+}
+
+impl ShutdownSignal for Arc<AtomicBool> {
+    fn wait(self) {
+        while !self.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+impl Server {
+    /// Binds a listener, setting `IPV6_V6ONLY` on IPv6 addresses if configured.
+    fn bind_listener(addr: SocketAddr, config: &Config) -> Result<TcpListener, Error> {
+        let Some(only_v6) = config.ipv6_only.filter(|_| addr.is_ipv6()) else {
+            return TcpListener::bind(addr);
+        };
+        // The option must be set before binding, which std does not allow
+        let socket = Socket::new(Domain::IPV6, Type::STREAM, Some(Protocol::TCP))?;
+        socket.set_only_v6(only_v6)?;
+        #[cfg(unix)]
+        socket.set_reuse_address(true)?;
+        socket.bind(&addr.into())?;
+        socket.listen(128)?;
+        Ok(socket.into())
+    }
+
+    /// Accepts connections on a listener and handles each in a new thread until shutdown.
+    ///
+    /// # Parameters
+    /// - 'listener' : The bound listener to accept connections from.
+    /// - 'routes' : The routes shared by every connection.
+    /// - 'config' : The server-wide settings shared by every connection.
+    /// - 'shutdown' : Set to stop accepting; checked after every accepted connection.
+    fn accept_loop<R: Router>(
+        listener: TcpListener,
+        routes: Routes<R>,
+        config: Arc<Config>,
+        shutdown: Arc<AtomicBool>,
+    ) {
+        let at_capacity = || match config.max_connections {
+            Some((limit, at_capacity)) => {
+                let full = config.active_connections.load(Ordering::SeqCst) >= limit;
+                Some(at_capacity).filter(|_| full)
+            }
+            None => None,
+        };
+
+        let mut accept_errors = 0;
+        let overloaded = |accept_errors| {
+            config.overload.as_ref().is_some_and(|policy| {
+                policy.is_overloaded(&Load {
+                    active_connections: config.active_connections.load(Ordering::SeqCst),
+                    max_connections: config.max_connections.map(|(limit, _)| limit),
+                    accept_errors,
+                })
+            })
+        };
+
+        // Listen for incoming connections
+        loop {
+            // Leave new clients in the backlog until a connection closes
+            while at_capacity() == Some(AtCapacity::Wait) && !shutdown.load(Ordering::SeqCst) {
+                thread::sleep(Duration::from_millis(10));
+            }
+            let stream = listener.accept().map(|(stream, _)| stream);
+            if shutdown.load(Ordering::SeqCst) {
+                break;
+            }
+            // The policy sees the failures that came before this connection
+            let failed_before = accept_errors;
+            if stream.is_ok() {
+                accept_errors = 0;
+                config.counters.connection();
+            }
+            match stream {
+                Ok(stream) if at_capacity() == Some(AtCapacity::Reject) => {
+                    log_debug!("Connection rejected: at the connection limit");
+                    Server::reject(stream, &config);
+                }
+                Ok(stream) if overloaded(failed_before) => {
+                    log_debug!("Connection rejected: the server is overloaded");
+                    Server::reject(stream, &config);
+                }
+                Ok(stream) => {
+                    let routes = routes.clone();
+                    let config = Arc::clone(&config);
+                    let shutdown = Arc::clone(&shutdown);
+                    let active = ActiveConnection::new(&config.active_connections);
+                    thread::spawn(move || {
+                        let _active = active;
+                        if let Err(e) = Server::handle_connection(routes, config, stream, &shutdown)
+                        {
+                            // A client going away mid-response is routine, not a failure
+                            if Server::is_timeout(&e) {
+                                log_debug!("Connection timed out: {}", e);
+                            } else if !Server::is_disconnect(&e) {
+                                log_error!("Connection failed: {}", e);
+                            }
+                        }
+                    });
+                }
+                Err(e) => {
+                    accept_errors += 1;
+                    log_error!("Failed to accept connection: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Reports whether more requests are being served than `shed_load` allows, counting the
+    /// request asking.
+    fn is_shedding(config: &Config) -> bool {
+        config
+            .shed_load
+            .is_some_and(|limit| config.counters.in_flight() > limit)
+    }
+
+    /// Turns on TCP keepalive for an accepted connection if configured.
+    fn set_tcp_keepalive(socket: SockRef, config: &Config) -> Result<(), Error> {
+        match config.tcp_keepalive {
+            Some(idle) => socket.set_tcp_keepalive(&TcpKeepalive::new().with_time(idle)),
+            None => Ok(()),
+        }
+    }
+
+    /// Answers a connection the server has no room for with 503 and closes it, without
+    /// reading its request.
+    fn reject(mut stream: TcpStream, config: &Config) {
+        let response = HttpResponse::status(503).insert_header("Retry-After", "1");
+        let response = Server::render_error(response, config).close_connection();
+        let res = Server::generate_http_response(
+            &response,
+            config,
+            false,
+            &Server::generate_request_id(),
+        );
+        let _ = stream.set_write_timeout(Some(Duration::from_secs(1)));
+        let _ = stream.write_all(&res);
+    }
+
+    /// Handles the incoming TCP connection, processes the HTTP request, and sends back a response.
+    ///
+    /// HTTP/1.1 connections are kept alive: requests are served one after another until the
+    /// client sends `Connection: close`, closes the connection, or a response is marked with
+    /// `HttpResponse::close_connection`. HTTP/1.0 connections are only kept alive when the
+    /// client asks with `Connection: keep-alive`, which the response then confirms.
+    ///
+    /// # Parameters
+    /// - `routes`: The router containing the routing information. This is used to match the
+    ///   incoming HTTP request's path and method to the appropriate handler function.
+    /// - `config`: The server-wide settings applied to the response.
+    /// - `stream`: The TCP stream representing the connection to the client. This is used to read
+    ///   the request and send the response back to the client. The stream is mutable because it will
+    ///   be written to as part of generating the HTTP response.
+    /// - `shutdown`: Set when the server is shutting down; the connection is then closed after
+    ///   the response in progress instead of being kept alive.
+    ///
+    /// With the `tracing` feature enabled, each request runs inside a `request` span carrying a
+    /// generated request ID, the method and the path; the status code and latency are recorded on
+    /// the span before it closes.
+    fn handle_connection<R: Router>(
+        routes: Routes<R>,
+        config: Arc<Config>,
+        mut stream: TcpStream,
+        shutdown: &AtomicBool,
+    ) -> Result<(), Error> {
+        stream.set_read_timeout(config.read_timeout)?;
+        stream.set_write_timeout(config.write_timeout)?;
+        Server::set_tcp_keepalive(SockRef::from(&stream), &config)?;
+        let client = stream.peer_addr().ok().map(|addr| addr.ip());
+
+        // Requests are read through a buffer that outlives each request, since a client may
+        // already have sent the next one
+        let mut reader = BufReader::new(RequestReader::new(stream.try_clone()?, &config));
+        let interim_stream = Arc::new(stream.try_clone()?);
+        let mut served = 0;
+        loop {
+            // The client closing the connection between requests ends it normally
+            if reader.fill_buf()?.is_empty() {
+                return Ok(());
+            }
+            // Held until the response is sent, for `serve_until` to wait on
+            let _answering = ActiveConnection::new(&config.answering);
+
+            #[cfg(feature = "tracing")]
+            let start = std::time::Instant::now();
+            #[cfg(feature = "tracing")]
+            let span = tracing::info_span!(
+                "request",
+                request_id = tracing::field::Empty,
+                method = tracing::field::Empty,
+                path = tracing::field::Empty,
+                status = tracing::field::Empty,
+                latency_us = tracing::field::Empty,
+            );
+            #[cfg(feature = "tracing")]
+            let _entered = span.enter();
+
+            // Upload routes stream the body themselves, so it is only read for other requests
+            let deadline = config
+                .request_deadline
+                .map(|deadline| Instant::now() + deadline);
+            reader.get_mut().deadline = deadline;
+            let request = Server::parse_head(&mut reader, &config).and_then(|mut request| {
+                if !config.uploads.iter().any(|upload| upload.matches(&request)) {
+                    Server::read_body(&mut reader, &mut request, &config)?;
+                }
+                Ok(request)
+            });
+            reader.get_mut().end_deadline()?;
+            let request = match request {
+                Ok(request) => Ok(request),
+                Err(ParseError::Status(status_code)) => Err(status_code),
+                Err(ParseError::Http2) => {
+                    stream.write_all(&HTTP2_REFUSAL)?;
+                    return Ok(());
+                }
+                Err(ParseError::Io(_)) if deadline.is_some_and(|d| Instant::now() >= d) => {
+                    log_debug!("Request not received within its deadline");
+                    Err(408)
+                }
+                Err(ParseError::Io(e)) => return Err(e),
+            };
+
+            let proxy = request.as_ref().ok().and_then(|request| {
+                config
+                    .proxies
+                    .iter()
+                    .find(|proxy| proxy.matches(&request.path))
+            });
+            let keep_alive = match (proxy, request) {
+                (_, Ok(request)) if Server::tunnel_allowed(&config, &request) => {
+                    // The tunnel takes the connection over until either side closes it
+                    return proxy::tunnel(&request, &config, reader, &mut stream);
+                }
+                (Some(proxy), Ok(request)) => {
+                    // The proxied response is delimited by closing the connection
+                    proxy.forward(&request, &config, &mut stream)?;
+                    false
+                }
+                (_, request) => {
+                    // Upload routes and requests that failed to parse may leave the body unread
+                    let unread = request.as_ref().map_or(true, |request| {
+                        config.uploads.iter().any(|upload| upload.matches(request))
+                    });
+                    let interim = Interim::new(&interim_stream);
+                    let request = request.map(|mut request| {
+                        // Interim responses are new in HTTP/1.1; older clients never get them
+                        if request.version == "HTTP/1.1" {
+                            request.interim = interim.clone();
+                        }
+                        request
+                    });
+                    // Once the server is shutting down or the connection has carried its share of
+                    // requests, finish it with this response
+                    served += 1;
+                    let persistent =
+                        !shutdown.load(Ordering::SeqCst) && served < config.max_requests;
+                    let body: &mut dyn Read = &mut reader;
+                    let response =
+                        Server::serve(&routes, &config, request, Some(body), client, persistent);
+                    // A handler still running past its timeout must not write into the response
+                    interim.close();
+                    let keep_alive = response.keep_alive;
+                    Server::send_response(&mut stream, response)?;
+                    if !keep_alive && unread {
+                        Server::lingering_close(&mut stream);
+                    }
+                    keep_alive
+                }
+            };
+
+            #[cfg(feature = "tracing")]
+            span.record("latency_us", start.elapsed().as_micros() as u64);
+
+            // A shutdown that began while the handler ran still ends the connection here
+            if !keep_alive || shutdown.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Closes a connection after its response while the client may still be sending a request
+    /// body the server did not read, e.g. one refused with 413.
+    ///
+    /// Closing a socket with unread data makes the OS reset the connection, and the reset can
+    /// discard the response before the client reads it, or fail the client's write of the
+    /// body before it gets to reading. So the sending side is shut down first, then whatever
+    /// arrives is discarded until the client closes too, for at most `LINGER` and
+    /// `LINGER_LIMIT` bytes.
+    fn lingering_close(stream: &mut TcpStream) {
+        const LINGER: Duration = Duration::from_secs(2);
+        const LINGER_LIMIT: usize = 1024 * 1024;
+
+        if stream.shutdown(Shutdown::Write).is_err() {
+            return;
+        }
+        let deadline = Instant::now() + LINGER;
+        let mut buffer = [0; 8192];
+        let mut discarded = 0;
+        while discarded < LINGER_LIMIT {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() || stream.set_read_timeout(Some(remaining)).is_err() {
+                break;
+            }
+            match stream.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => discarded += read,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => break,
+            }
+        }
+        if discarded > 0 {
+            log_debug!(
+                "Discarded {} unread request bytes before closing",
+                discarded
+            );
+        }
+    }
+
+    /// Reads and parses the request line and headers of an HTTP request from a reader,
+    /// leaving the body, if any, unread.
+    ///
+    /// # Parameters
+    /// - 'reader' : The buffered source of the request, e.g. a TCP stream or an in-memory buffer.
+    /// - 'config' : The server-wide settings holding the request limits.
+    ///
+    /// # Returns
+    /// The parsed `Request`, or a `ParseError` telling whether the input ended early or failed
+    /// (`Io`, with `ErrorKind::UnexpectedEof` when the request is incomplete) or the request must
+    /// be answered with an error status (`Status`).
+    ///
+    /// The request line and headers must be valid UTF-8, or the request is answered with 400.
+    /// Header values in Latin-1 (`obs-text`, long deprecated) are rejected too rather than
+    /// guessed at; non-ASCII values must be sent percent- or otherwise encoded.
+    fn parse_head<R: BufRead>(reader: &mut R, config: &Config) -> Result<Request, ParseError> {
+        let start = Instant::now();
+        let mut header_parsed = false;
+        let mut method = Option::None;
+        let mut path = String::new();
+        let mut query = Query::default();
+        let mut headers = HashMap::new();
+        let mut authority = None;
+        let mut version = String::new();
+        let mut request_line = true;
+        // One buffer serves every line, so a request with many headers reuses its allocation
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            let is_request_line = request_line;
+            let bytes_read = if request_line {
+                request_line = false;
+                // Bound the read so an oversized request target is never buffered in full
+                let limit = config.max_request_line as u64 + 2;
+                let bytes_read = Read::take(&mut *reader, limit)
+                    .read_line(&mut line)
+                    .map_err(Server::invalid_utf8)?;
+                if bytes_read as u64 == limit && !line.ends_with('\n') {
+                    return Err(ParseError::Status(414));
+                }
+                bytes_read
+            } else {
+                reader.read_line(&mut line).map_err(Server::invalid_utf8)?
+            };
+
+            if bytes_read == 0 {
+                break;
+            }
+
+            if line == "\r\n" {
+                header_parsed = true;
+                break;
+            }
+
+            if is_request_line {
+                // An HTTP/2 client starts with a preface whose first line looks like a request
+                if line == "PRI * HTTP/2.0\r\n" {
+                    log_warn!("Refused HTTP/2 connection preface");
+                    return Err(ParseError::Http2);
+                }
                 let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 2 {
-                    method = match parts[0] {
-                        "GET" => Some(HttpMethod::GET),
-                        "POST" => Some(HttpMethod::POST),
-                        _ => None,
+                if parts.len() < 2 {
+                    log_warn!("Rejected malformed request line");
+                    return Err(ParseError::Status(400));
+                }
+                method = parts[0].parse().ok();
+                // A request line without a version is from HTTP/1.0 or earlier
+                version = parts.get(2).unwrap_or(&"HTTP/1.0").to_string();
+                // Only HTTP/1.x is spoken here; a request of another major version, e.g.
+                // HTTP/2 sent in the clear, would be misread as one
+                let Some((major, minor)) = version
+                    .strip_prefix("HTTP/")
+                    .and_then(|number| number.split_once('.'))
+                    .filter(|(major, minor)| Server::is_digit(major) && Server::is_digit(minor))
+                else {
+                    log_warn!("Rejected request with a malformed version");
+                    return Err(ParseError::Status(400));
+                };
+                if major != "1" {
+                    log_warn!("Rejected HTTP/{}.{} request", major, minor);
+                    return Err(ParseError::Status(505));
+                }
+                // An absolute-form target names the host itself; route on its path
+                let target = match Server::split_absolute_form(parts[1]) {
+                    Some((target_authority, target)) => {
+                        authority = Some(target_authority.to_string());
+                        target
+                    }
+                    None => parts[1],
+                };
+                // Routing only looks at the path; the query string is parsed separately
+                let (target_path, query_string) = target.split_once('?').unwrap_or((target, ""));
+                // `OPTIONS *` asks about the server and a CONNECT target names a host and
+                // port, so neither is a path to normalize or rewrite
+                let is_path = target_path != "*" && method != Some(HttpMethod::CONNECT);
+                path = if config.normalize_paths && is_path {
+                    Server::normalize_path(target_path)
+                } else if target_path.is_empty() {
+                    "/".to_string()
+                } else {
+                    target_path.to_string()
+                };
+                if let Some(rewrite) = config.path_rewrite.as_ref().filter(|_| is_path) {
+                    path = match rewrite(&path) {
+                        Cow::Borrowed(rewritten) if rewritten == path => path,
+                        rewritten if rewritten.is_empty() => "/".to_string(),
+                        rewritten => rewritten.into_owned(),
+                    };
+                }
+                query = Query::parse(query_string);
+
+                #[cfg(feature = "tracing")]
+                {
+                    let span = tracing::Span::current();
+                    span.record("method", parts[0]);
+                    span.record("path", parts[1]);
+                }
+            } else {
+                // A line that is not a header would otherwise be skipped, leaving the request
+                // open to different readings by this server and others in front of it
+                let Some((name, value)) = Server::parse_header_line(&line) else {
+                    return Err(ParseError::Status(400));
+                };
+                let name = name.to_lowercase();
+                // Lengths that disagree leave the end of the body open to different readings
+                if name == "content-length" && headers.get(&name).is_some_and(|l| l != value) {
+                    log_warn!("Rejected request with conflicting Content-Length headers");
+                    return Err(ParseError::Status(400));
+                }
+                headers.insert(name, value.to_string());
+            }
+        }
+
+        // The host of an absolute-form target takes precedence over the Host header
+        if let Some(authority) = authority {
+            headers.insert("host".to_string(), authority);
+        }
+
+        if !header_parsed {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "Incomplete header").into());
+        }
+
+        // Bodies are only read by length; a chunked one taken as empty would be read as the
+        // next request
+        if headers.contains_key("transfer-encoding") {
+            log_warn!("Refused request with a Transfer-Encoding");
+            return Err(ParseError::Status(501));
+        }
+
+        // A length that is not a plain number cannot delimit the body, nor be read as none
+        if let Some(length) = headers.get("content-length") {
+            if !length.bytes().all(|byte| byte.is_ascii_digit()) || length.parse::<u64>().is_err() {
+                log_warn!("Rejected request with an invalid Content-Length");
+                return Err(ParseError::Status(400));
+            }
+        }
+
+        // HTTP/1.1 requires a Host header, or an absolute-form target standing in for it
+        if version == "HTTP/1.1" && !headers.contains_key("host") {
+            log_warn!("Rejected HTTP/1.1 request without a Host header");
+            return Err(ParseError::Status(400));
+        }
+
+        let cookies = headers
+            .get("cookie")
+            .map(|header| cookie::parse_cookies(header))
+            .unwrap_or_default();
+        #[cfg(feature = "signed-cookies")]
+        let signed_cookies = config
+            .cookie_secret
+            .as_deref()
+            .map(|secret| cookie::verify_cookies(secret, &cookies))
+            .unwrap_or_default();
+        #[cfg(not(feature = "signed-cookies"))]
+        let signed_cookies = HashMap::new();
+
+        #[cfg(feature = "signed-cookies")]
+        let session = config
+            .sessions
+            .as_ref()
+            .map(|sessions| sessions.restore(&signed_cookies))
+            .unwrap_or_default();
+        #[cfg(not(feature = "signed-cookies"))]
+        let session = Session::default();
+
+        // Honor an ID assigned upstream (e.g. by a proxy) so logs line up across services
+        let request_id = headers
+            .get("x-request-id")
+            .filter(|id| Server::is_valid_request_id(id))
+            .cloned()
+            .unwrap_or_else(Server::generate_request_id);
+
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("request_id", request_id.as_str());
+
+        match method {
+            Some(method) => Ok(Request {
+                method,
+                path,
+                query,
+                params: Params::default(),
+                route: None,
+                headers,
+                cookies,
+                signed_cookies,
+                session,
+                body: Vec::new(),
+                version,
+                request_id,
+                start,
+                interim: Interim::default(),
+            }),
+            None => Err(ParseError::Status(405)),
+        }
+    }
+
+    /// Reads the body of a request, as delimited by its `Content-Length`, into `body`,
+    /// decompressing it if the `compression` feature is enabled.
+    ///
+    /// A body longer than `max_body` is refused with `Status` 413 before any of it is read.
+    fn read_body<R: BufRead>(
+        reader: &mut R,
+        request: &mut Request,
+        config: &Config,
+    ) -> Result<(), ParseError> {
+        let length = request.content_length();
+        if length > config.max_body as u64 {
+            log_warn!("Refused request body of {} bytes", length);
+            return Err(ParseError::Status(413));
+        }
+        // The buffer grows with the body as it arrives rather than by the length announced
+        let mut body = Vec::new();
+        Read::take(&mut *reader, length).read_to_end(&mut body)?;
+        if (body.len() as u64) < length {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "Incomplete body").into());
+        }
+
+        #[cfg(feature = "compression")]
+        let body = Server::decode_body(body, &mut request.headers, config)?;
+        #[cfg(not(feature = "compression"))]
+        let _ = config;
+        request.body = body;
+        Ok(())
+    }
+
+    /// Splits a header line into its name and value, trimmed of surrounding whitespace.
+    ///
+    /// # Returns
+    /// The name and value, or `None` if the line has no colon, the name is empty or holds
+    /// characters other than token characters (including whitespace, which also rules out
+    /// obsolete line folding), or the value holds control characters such as a bare CR,
+    /// which could smuggle a header past other parsers or split a response echoing it.
+    fn parse_header_line(line: &str) -> Option<(&str, &str)> {
+        let line = line
+            .strip_suffix("\r\n")
+            .or_else(|| line.strip_suffix('\n'))
+            .unwrap_or(line);
+        let Some((name, value)) = line.split_once(':') else {
+            log_warn!("Rejected header line without a colon");
+            return None;
+        };
+        if !Server::is_token(name) {
+            log_warn!("Rejected header with an invalid name");
+            return None;
+        }
+        if value.chars().any(|c| c.is_control() && c != '\t') {
+            log_warn!(
+                "Rejected header {} with control characters in its value",
+                name
+            );
+            return None;
+        }
+        Some((name, value.trim_matches([' ', '\t'])))
+    }
+
+    /// Reports whether a string is a single ASCII digit, as in a protocol version.
+    fn is_digit(s: &str) -> bool {
+        s.len() == 1 && s.as_bytes()[0].is_ascii_digit()
+    }
+
+    /// Reports whether a header name is an HTTP token: letters, digits and
+    /// ``!#$%&'*+-.^_`|~``, at least one.
+    fn is_token(name: &str) -> bool {
+        let is_token_char = |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c);
+        !name.is_empty() && name.chars().all(is_token_char)
+    }
+
+    /// Turns the error `read_line` reports for bytes that are not UTF-8 into a 400 response;
+    /// other errors are passed on.
+    fn invalid_utf8(error: Error) -> ParseError {
+        if error.kind() == ErrorKind::InvalidData {
+            log_warn!("Rejected request with a request line or header that is not UTF-8");
+            ParseError::Status(400)
+        } else {
+            error.into()
+        }
+    }
+
+    /// Dispatches a parsed request to its handler and renders the response to send.
+    ///
+    /// # Parameters
+    /// - 'routes' : A shared reference to the routes configuration.
+    /// - 'config' : The server-wide settings applied to the response.
+    /// - 'request' : The parsed request, or the error status to answer with when parsing failed.
+    /// - 'body' : The connection, positioned at the request body, for upload routes to stream
+    ///   it; `None` if the body, if any, was read into the request already.
+    /// - 'client' : The IP address of the client, for the access log.
+    /// - 'persistent' : Whether the connection can be kept open after this response; when
+    ///   `false` the response is sent with `Connection: close`.
+    ///
+    /// # Returns
+    /// The response to send, and whether the connection should be kept open for another
+    /// request.
+    fn serve<R: Router>(
+        routes: &Routes<R>,
+        config: &Config,
+        request: Result<Request, u16>,
+        body: Option<&mut dyn Read>,
+        client: Option<IpAddr>,
+        persistent: bool,
+    ) -> Outgoing {
+        let start = Instant::now();
+        let mut request = request;
+        let response = match &mut request {
+            Ok(request) => {
+                let in_flight = ActiveConnection::new(&config.counters.in_flight);
+                let response = if Server::is_shedding(config) {
+                    log_debug!("Request refused: too many requests in flight");
+                    let response = match &config.shed_response {
+                        Some(respond) => respond(request),
+                        None => HttpResponse::status(503).insert_header("Retry-After", "1"),
                     };
-                    path = parts[1].to_string();
+                    // An upload's body is still unread, so the next request cannot be found
+                    if config.uploads.iter().any(|upload| upload.matches(request)) {
+                        response.close_connection()
+                    } else {
+                        response
+                    }
+                } else {
+                    // A handler panicking gets its client a 500 rather than a dropped
+                    // connection; a body it was reading may be left half read, so the
+                    // connection ends
+                    let response = panic::catch_unwind(AssertUnwindSafe(|| {
+                        Server::processing_response(routes, config, request, body).resolve()
+                    }));
+                    response.unwrap_or_else(|_| {
+                        log_error!("Handler panicked");
+                        HttpResponse::status(500).close_connection()
+                    })
+                };
+                drop(in_flight);
+                #[cfg(feature = "signed-cookies")]
+                let response = match &config.sessions {
+                    Some(sessions) => sessions.commit(&request.session, response),
+                    None => response,
+                };
+                response
+            }
+            Err(status_code) => HttpResponse::new(*status_code, None),
+        };
+        let response = Server::render_error(response, config);
+        let response = Server::apply_default_type(response, config);
+
+        // After a request that could not be parsed, the next one cannot be found reliably.
+        // HTTP/1.0 has no chunked encoding, so a streamed body there ends with the connection
+        let keep_alive = persistent
+            && !response.close
+            && request.as_ref().is_ok_and(|request| {
+                Server::wants_keep_alive(request)
+                    && (request.version != "HTTP/1.0" || !response.is_chunked())
+            });
+        let response = match &request {
+            // HTTP/1.0 clients only reuse the connection when told it stays open
+            Ok(request) if keep_alive && request.version == "HTTP/1.0" => {
+                if response.has_header("Connection") {
+                    response
+                } else {
+                    response.insert_header("Connection", "keep-alive")
+                }
+            }
+            _ if keep_alive => response,
+            _ => response.close_connection(),
+        };
+
+        let mut response = response;
+        let is_head = request
+            .as_ref()
+            .is_ok_and(|request| request.method == HttpMethod::HEAD);
+        let bytes = match &request {
+            Ok(request) => {
+                Server::generate_http_response(&response, config, is_head, &request.request_id)
+            }
+            Err(_) => Server::generate_http_response(
+                &response,
+                config,
+                false,
+                &Server::generate_request_id(),
+            ),
+        };
+        let chunked = response.is_chunked();
+        let stream = response.stream.take().filter(|_| !is_head);
+
+        // Post-request point: the response is final, record it
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("status", response.status_code);
+        config.counters.response(response.status_code);
+        if let Ok(request) = &request {
+            Server::report_error(request, &response, config);
+        }
+        if let Some(metrics) = &config.metrics {
+            let (method, path) = match &request {
+                // Keyed by route pattern, so `/users/1` and `/users/2` count as one, and
+                // requests without a route share a single key however many paths they ask for
+                Ok(request) => (
+                    request.method.as_str(),
+                    request.route.as_deref().unwrap_or(""),
+                ),
+                Err(_) => ("", ""),
+            };
+            metrics.record(method, path, response.status_code, start.elapsed());
+        }
+        if let Some(access_log) = &config.access_log {
+            let request = request.as_ref().ok();
+            let size = match request {
+                Some(request) if request.method == HttpMethod::HEAD => 0,
+                _ => response.body.as_ref().map_or(0, |body| body.len()),
+            };
+            access_log.write(client, request, response.status_code, size);
+        }
+
+        // Trailers can only follow a chunked body
+        let trailers = response.trailers.take().filter(|_| chunked);
+        Outgoing {
+            bytes,
+            stream,
+            chunked,
+            trailers,
+            keep_alive,
+        }
+    }
+
+    /// Calls the `on_error` hook if a response is a server error.
+    pub(crate) fn report_error(request: &Request, response: &HttpResponse, config: &Config) {
+        if let Some(on_error) = config
+            .on_error
+            .as_ref()
+            .filter(|_| response.status_code >= 500)
+        {
+            on_error(request, response);
+        }
+    }
+
+    /// Fills in the body of an error response sent without one, using the renderer set with
+    /// `error_body`. Other responses are returned unchanged.
+    fn render_error(mut response: HttpResponse, config: &Config) -> HttpResponse {
+        let Some(render) = &config.error_body else {
+            return response;
+        };
+        if response.status_code >= 400 && response.body.is_none() && response.stream.is_none() {
+            let rendered = render(response.status_code);
+            response.headers.extend(rendered.headers);
+            response.body = rendered.body;
+        }
+        response
+    }
+
+    /// Replaces the `text/plain` that `HttpResponse::new` defaults to with the type set with
+    /// `Server::default_content_type`, or removes it. Types set explicitly are kept.
+    fn apply_default_type(mut response: HttpResponse, config: &Config) -> HttpResponse {
+        let Some(default_type) = &config.default_type else {
+            return response;
+        };
+        let is_default =
+            response.headers.get("Content-Type").map(String::as_str) == Some("text/plain");
+        if response.implicit_type && is_default {
+            match default_type {
+                Some(default_type) => {
+                    response
+                        .headers
+                        .insert("Content-Type".to_string(), default_type.clone());
+                }
+                None => {
+                    response.headers.remove("Content-Type");
+                }
+            }
+        }
+        response
+    }
+
+    /// Reports whether the client is willing to send further requests on the connection.
+    ///
+    /// # Returns
+    /// `true` for HTTP/1.1 requests without `Connection: close`, and for HTTP/1.0 requests
+    /// with `Connection: keep-alive`, the opposite default.
+    fn wants_keep_alive(request: &Request) -> bool {
+        let has_token = |wanted: &str| {
+            request.headers.get("connection").is_some_and(|connection| {
+                connection
+                    .split(',')
+                    .any(|token| token.trim().eq_ignore_ascii_case(wanted))
+            })
+        };
+        match request.version.as_str() {
+            "HTTP/1.1" => !has_token("close"),
+            "HTTP/1.0" => has_token("keep-alive") && !has_token("close"),
+            _ => false,
+        }
+    }
+
+    /// Undoes the `Content-Encoding` of a request body.
+    ///
+    /// On success the `content-encoding` header is removed and `content-length` updated, so the
+    /// handler sees the body as if it had been sent uncompressed.
+    ///
+    /// # Returns
+    /// The decoded body, or `Status` 400 for a corrupt gzip stream, 413 for a body larger than
+    /// the configured limit once decompressed, or 415 for an unsupported encoding.
+    #[cfg(feature = "compression")]
+    fn decode_body(
+        body: Vec<u8>,
+        headers: &mut HashMap<String, String>,
+        config: &Config,
+    ) -> Result<Vec<u8>, ParseError> {
+        let Some(encoding) = headers.get("content-encoding") else {
+            return Ok(body);
+        };
+        let encoding = encoding.trim().to_lowercase();
+        if encoding == "identity" {
+            return Ok(body);
+        }
+        if encoding != "gzip" && encoding != "x-gzip" {
+            return Err(ParseError::Status(415));
+        }
+
+        // Read one byte past the limit to tell a body at the limit from a larger one
+        let limit = config.max_decompressed_body as u64;
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(body.as_slice())
+            .take(limit + 1)
+            .read_to_end(&mut decoded)
+            .map_err(|_| ParseError::Status(400))?;
+        if decoded.len() as u64 > limit {
+            return Err(ParseError::Status(413));
+        }
+
+        headers.remove("content-encoding");
+        headers.insert("content-length".to_string(), decoded.len().to_string());
+        Ok(decoded)
+    }
+
+    /// Splits an absolute-form request target, as sent to proxies, into its authority and the
+    /// origin-form rest.
+    ///
+    /// # Parameters
+    /// - 'target' : The request target, e.g. `http://example.com:8080/path?query`.
+    ///
+    /// # Returns
+    /// The authority (`example.com:8080`) and the path with query (`/path?query`, empty when
+    /// the target has no path), or `None` if the target is not an `http` or `https` URI.
+    fn split_absolute_form(target: &str) -> Option<(&str, &str)> {
+        let (scheme, rest) = target.split_once("://")?;
+        if !scheme.eq_ignore_ascii_case("http") && !scheme.eq_ignore_ascii_case("https") {
+            return None;
+        }
+        let end = rest.find(['/', '?']).unwrap_or(rest.len());
+        Some(rest.split_at(end))
+    }
+
+    /// Removes empty segments from a path, e.g. `/users//42/` becomes `/users/42`. Paths
+    /// without any other segment, such as `//` or an empty one, become `/`.
+    fn normalize_path(path: &str) -> String {
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        format!("/{}", segments.join("/"))
+    }
+
+    /// Generates an identifier for correlating everything logged about one request.
+    ///
+    /// # Returns
+    /// A 16-digit hexadecimal string, unique within the process and unpredictable across runs.
+    fn generate_request_id() -> String {
+        use std::{
+            collections::hash_map::RandomState,
+            hash::{BuildHasher, Hasher},
+            sync::atomic::{AtomicU64, Ordering},
+            sync::OnceLock,
+        };
+
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        static STATE: OnceLock<RandomState> = OnceLock::new();
+
+        let mut hasher = STATE.get_or_init(RandomState::new).build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        format!("{:016x}", hasher.finish())
+    }
+
+    /// Checks that a client-supplied request ID is safe to log and echo back.
+    ///
+    /// # Parameters
+    /// - 'id' : The value of the incoming `X-Request-Id` header.
+    ///
+    /// # Returns
+    /// `true` if the ID is non-empty, at most 200 characters and made of visible ASCII only.
+    fn is_valid_request_id(id: &str) -> bool {
+        !id.is_empty() && id.len() <= 200 && id.bytes().all(|b| b.is_ascii_graphic())
+    }
+
+    /// Reports whether an I/O error means the client closed the connection.
+    ///
+    /// # Parameters
+    /// - 'error' : The error returned while reading from or writing to the client.
+    ///
+    /// # Returns
+    /// `true` for broken pipes, resets and aborts, which happen whenever a client cancels a
+    /// request (e.g. a browser navigating away) and are not worth logging.
+    fn is_disconnect(error: &Error) -> bool {
+        matches!(
+            error.kind(),
+            ErrorKind::BrokenPipe | ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted
+        )
+    }
+
+    /// Reports whether an I/O error means a read or write timeout expired.
+    ///
+    /// # Returns
+    /// `true` for the errors a socket timeout set with `set_read_timeout` or
+    /// `set_write_timeout` produces, which is `WouldBlock` on Unix and `TimedOut` on Windows.
+    fn is_timeout(error: &Error) -> bool {
+        matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+    }
+
+    /// Processes the HTTP response based on the method and path, invoking the registered handler.
+    ///
+    /// # Parameters
+    /// - 'routes' : A shared reference to the routes configuration.
+    /// - 'request' : The parsed request, passed on to the handler.
+    ///
+    /// # Returns
+    /// The generated HttpResponse based on the handler or a 404 response if no handler is found.
+    /// HEAD requests are served by the GET route for the same path. TRACE requests are echoed
+    /// or refused with 405, depending on `Server::trace`.
+    fn processing_response<R: Router>(
+        routes: &Routes<R>,
+        config: &Config,
+        request: &mut Request,
+        body: Option<&mut dyn Read>,
+    ) -> HttpResponse {
+        if request.method == HttpMethod::TRACE {
+            return if config.trace {
+                Server::trace_echo(request)
+            } else {
+                HttpResponse::status(405)
+            };
+        }
+        // Approved tunnels are opened before getting here. Whatever the client sends next may
+        // be meant for the tunnel rather than be another request, so the connection ends
+        if request.method == HttpMethod::CONNECT {
+            let status_code = if config.tunnel.is_some() { 403 } else { 405 };
+            return HttpResponse::new(status_code, None).close_connection();
+        }
+        if let Some(upload) = config.uploads.iter().find(|upload| upload.matches(request)) {
+            request.route = Some(upload.path.clone());
+            return match body {
+                Some(body) => upload.receive(request, body),
+                // Buffered already, e.g. by the event loop
+                None => upload.receive(request, &mut request.body.clone().as_slice()),
+            };
+        }
+        let method = match request.method {
+            HttpMethod::HEAD => HttpMethod::GET,
+            ref method => method.clone(),
+        };
+        let found = routes.read().find(&method, &request.path);
+        let route = match found {
+            Some(found) => {
+                let params = found
+                    .params
+                    .into_iter()
+                    .map(|(name, value)| Some((name, query::percent_decode(&value)?)))
+                    .collect::<Option<HashMap<_, _>>>();
+                let Some(params) = params else {
+                    return HttpResponse::status(400);
+                };
+                request.params = Params::from(params);
+                request.route = found.route.pattern.clone();
+                Some(found.route)
+            }
+            None => Server::static_route(config, &method, &request.path),
+        };
+        let route = match route {
+            Some(route) => route,
+            None => {
+                let allowed = Server::allowed_methods(routes, config, &request.path);
+                if request.method == HttpMethod::OPTIONS && !allowed.is_empty() {
+                    let allow = Server::allow_header(&allowed);
+                    let mut response = HttpResponse::status(204);
+                    response.headers.remove("Content-Type"); // There is no body to describe.
+                    return response.insert_header("Allow", &allow);
+                }
+                // The path has routes for other methods, so it is not unknown
+                if !allowed.is_empty() {
+                    return Server::not_allowed(config, &request.path, allowed);
+                }
+                match &config.fallback {
+                    Some(fallback) => Route::new(fallback.clone()),
+                    None => return HttpResponse::status(404),
+                }
+            }
+        };
+        // A route's own timeout overrides the server-wide one
+        match route.timeout.or(config.handler_timeout) {
+            Some(timeout) => Server::run_with_timeout(route.handler, request, timeout),
+            None => (route.handler)(request),
+        }
+    }
+
+    /// Builds the 405 response for a path, with the handler set with `method_not_allowed`
+    /// if any, and the `Allow` header.
+    fn not_allowed(config: &Config, path: &str, allowed: Vec<HttpMethod>) -> HttpResponse {
+        let allow = Server::allow_header(&allowed);
+        let response = match &config.method_not_allowed {
+            Some(handler) => handler(path, &allowed),
+            None => HttpResponse::status(405),
+        };
+        if response.has_header("Allow") {
+            response
+        } else {
+            response.insert_header("Allow", &allow)
+        }
+    }
+
+    /// Lists the methods a path can be requested with: those of its routes, GET for paths
+    /// under a static directory, HEAD along with GET, and OPTIONS, each once and in a fixed
+    /// order. Shared by 405 and OPTIONS responses; see `allowed_methods_for`.
+    ///
+    /// # Returns
+    /// The methods, or an empty list if the path is unknown. For `*`, every method routes
+    /// can be registered for.
+    fn allowed_methods<R: Router>(
+        routes: &Routes<R>,
+        config: &Config,
+        path: &str,
+    ) -> Vec<HttpMethod> {
+        // The order methods are listed in, whatever order they were found in
+        const ORDER: [HttpMethod; 5] = [
+            HttpMethod::GET,
+            HttpMethod::HEAD,
+            HttpMethod::POST,
+            HttpMethod::PATCH,
+            HttpMethod::OPTIONS,
+        ];
+
+        let mut found = if path == "*" {
+            vec![HttpMethod::GET, HttpMethod::POST, HttpMethod::PATCH]
+        } else {
+            routes.read().allowed_methods(path)
+        };
+        if config.uploads.iter().any(|upload| upload.path == path) {
+            found.push(HttpMethod::POST);
+        }
+        if config.static_dirs.iter().any(|dir| dir.matches(path)) {
+            found.push(HttpMethod::GET);
+        }
+        if found.is_empty() {
+            return found;
+        }
+        // HEAD requests are served by GET routes
+        if found.contains(&HttpMethod::GET) {
+            found.push(HttpMethod::HEAD);
+        }
+        found.push(HttpMethod::OPTIONS);
+        ORDER.into_iter().filter(|m| found.contains(m)).collect()
+    }
+
+    /// Formats methods as the value of an `Allow` header, e.g. `GET, HEAD, OPTIONS`.
+    fn allow_header(allowed: &[HttpMethod]) -> String {
+        allowed
+            .iter()
+            .map(HttpMethod::as_str)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Finds the static directory serving a GET request's path, as a route.
+    fn static_route(config: &Config, method: &HttpMethod, path: &str) -> Option<Route> {
+        if *method != HttpMethod::GET {
+            return None;
+        }
+        let dir = config.static_dirs.iter().find(|dir| dir.matches(path))?;
+        let (dir, normalized) = (dir.clone(), config.normalize_paths);
+        Some(Route::new(Arc::new(move |request| {
+            dir.serve(request, normalized)
+        })))
+    }
+
+    /// Reports whether a request is a CONNECT to a target approved with `Server::tunnel`.
+    fn tunnel_allowed(config: &Config, request: &Request) -> bool {
+        request.method == HttpMethod::CONNECT
+            && config
+                .tunnel
+                .as_ref()
+                .is_some_and(|allow| allow(&request.path))
+    }
+
+    /// Echoes a TRACE request back to the client, without the headers carrying credentials.
+    fn trace_echo(request: &Request) -> HttpResponse {
+        const SENSITIVE: [&str; 3] = ["authorization", "cookie", "proxy-authorization"];
+        let mut echo = format!("TRACE {}", request.path);
+        if !request.query.as_str().is_empty() {
+            echo.push('?');
+            echo.push_str(request.query.as_str());
+        }
+        echo.push_str(&format!(" {}\r\n", request.version));
+        for (name, value) in &request.headers {
+            if !SENSITIVE.contains(&name.as_str()) {
+                echo.push_str(&format!("{}: {}\r\n", name, value));
+            }
+        }
+        HttpResponse::with_content_type(200, "message/http", Some(echo))
+    }
+
+    /// Runs a handler on its own thread, waiting a limited time for its response.
+    ///
+    /// # Returns
+    /// The handler's response, 503 if it did not finish within `timeout`, or 500 if it panicked.
+    fn run_with_timeout(handler: Handler, request: &Request, timeout: Duration) -> HttpResponse {
+        let (sender, receiver) = mpsc::channel();
+        let request = request.clone();
+        thread::spawn(move || {
+            // The receiver is gone if the handler took too long; its response is discarded
+            let _ = sender.send(handler(&request));
+        });
+        match receiver.recv_timeout(timeout) {
+            Ok(response) => response,
+            Err(RecvTimeoutError::Timeout) => {
+                log_error!("Handler timed out after {:?}", timeout);
+                HttpResponse::status(503)
+            }
+            Err(RecvTimeoutError::Disconnected) => HttpResponse::status(500),
+        }
+    }
+
+    /// Sends an HTTP response to the client.
+    ///
+    /// # Parameters
+    /// - 'stream' : The TCP stream to send the response over.
+    /// - 'response' : The response content (HTTP status, headers, body) to be sent.
+    ///
+    /// # Notes
+    /// This function writes the full HTTP response to the provided stream, reading a streamed
+    /// body to its end. The write error, if any, is returned so the caller can stop processing a
+    /// dead connection.
+    fn send_response(stream: &mut TcpStream, response: Outgoing) -> Result<(), Error> {
+        Server::write_fully(stream, &response.bytes)?;
+        match response.stream {
+            Some(body) => Server::write_body(stream, body, response.chunked, response.trailers),
+            None => Ok(()),
+        }
+    }
+
+    /// Copies a streamed response body to the client as it is read.
+    ///
+    /// # Parameters
+    /// - 'writer' : Where the body goes, usually the client connection.
+    /// - 'body' : The body to read until its end.
+    /// - 'chunked' : Whether to frame the body with chunked transfer encoding; otherwise it is
+    ///   copied as is and ends when the connection closes.
+    /// - 'trailers' : Computes the trailer fields sent after the last chunk, once the body has
+    ///   been read; only used when `chunked`.
+    ///
+    /// # Returns
+    /// An error if reading the body or writing failed. A chunked body is then left without its
+    /// terminating chunk, so the client can tell it was cut short; the connection must be
+    /// closed.
+    fn write_body<W: Write>(
+        writer: &mut W,
+        mut body: Box<dyn Read + Send>,
+        chunked: bool,
+        trailers: Option<Trailers>,
+    ) -> Result<(), Error> {
+        let mut buffer = [0; 8192];
+        loop {
+            let n = match body.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if chunked {
+                // Frame the chunk in one buffer so it goes out in as few writes as possible
+                let mut chunk = format!("{:x}\r\n", n).into_bytes();
+                chunk.extend_from_slice(&buffer[..n]);
+                chunk.extend_from_slice(b"\r\n");
+                Server::write_fully(writer, &chunk)?;
+            } else {
+                Server::write_fully(writer, &buffer[..n])?;
+            }
+        }
+        if chunked {
+            let mut last = String::from("0\r\n");
+            for (name, value) in trailers.map(|trailers| trailers()).unwrap_or_default() {
+                Server::push_header(&mut last, &name, &value);
+            }
+            last.push_str("\r\n");
+            Server::write_fully(writer, last.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Writes all of `bytes`, resuming after short writes and interruptions.
+    ///
+    /// # Returns
+    /// An error if the client stopped accepting data. When the write timeout elapses (reported
+    /// as `WouldBlock` or `TimedOut`) the error keeps its kind and tells how much was sent, as
+    /// the response is then cut off mid-way and the connection must be closed.
+    fn write_fully<W: Write>(writer: &mut W, bytes: &[u8]) -> Result<(), Error> {
+        let mut written = 0;
+        while written < bytes.len() {
+            match writer.write(&bytes[written..]) {
+                Ok(0) => {
+                    return Err(Error::new(
+                        ErrorKind::WriteZero,
+                        format!(
+                            "Connection closed after {} of {} bytes",
+                            written,
+                            bytes.len()
+                        ),
+                    ));
                 }
+                Ok(n) => written += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) if Server::is_timeout(&e) => {
+                    return Err(Error::new(
+                        e.kind(),
+                        format!("Write timed out after {} of {} bytes", written, bytes.len()),
+                    ));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Orders headers for sending, so the same response is always sent byte for byte the same:
+    /// `Content-Type` and `Content-Length` first, the others alphabetically by name, ignoring
+    /// case.
+    fn sorted_headers(headers: &HashMap<String, String>) -> Vec<(&String, &String)> {
+        let rank = |name: &str| {
+            let name = name.to_ascii_lowercase();
+            let first = match name.as_str() {
+                "content-type" => 0,
+                "content-length" => 1,
+                _ => 2,
+            };
+            (first, name)
+        };
+        let mut sorted: Vec<_> = headers.iter().collect();
+        sorted.sort_by_cached_key(|(name, _)| rank(name));
+        sorted
+    }
+
+    /// Generates the full HTTP response string, including status code, headers, and body.
+    ///
+    /// # Parameters
+    /// - 'response' : The HttpResponse object containing status, headers, and body.
+    /// - 'config' : The server settings; its default headers are added unless the handler
+    ///   already set a header of the same name.
+    /// - 'is_head' : Whether the request was a HEAD request. The headers, including the
+    ///   Content-Length of the body, are sent unchanged but the body bytes are omitted.
+    /// - 'request_id' : The request's ID, echoed in the `X-Request-Id` header unless the handler
+    ///   already set one.
+    ///
+    /// # Returns
+    /// A vector of bytes representing the full HTTP response.
+    fn generate_http_response(
+        response: &HttpResponse,
+        config: &Config,
+        is_head: bool,
+        request_id: &str,
+    ) -> Vec<u8> {
+        let reason = match &response.reason {
+            // A line break would end the status line early and let the rest pose as headers
+            Some(reason) if reason.chars().any(|c| c.is_control() && c != '\t') => {
+                log_error!("Reason phrase dropped: it holds control characters");
+                response.get_status_message()
+            }
+            Some(reason) => reason.as_str(),
+            None => response.get_status_message(),
+        };
+        let mut response_string = format!("HTTP/1.1 {} {}\r\n", response.status_code, reason);
+        for (key, value) in Server::sorted_headers(&response.headers) {
+            Server::push_header(&mut response_string, key, value);
+        }
+        for (key, value) in Server::sorted_headers(&config.default_headers) {
+            if !response.has_header(key) {
+                Server::push_header(&mut response_string, key, value);
+            }
+        }
+        if !response.has_header("X-Request-Id") {
+            response_string.push_str(&format!("X-Request-Id: {}\r\n", request_id));
+        }
+        for cookie in &response.cookies {
+            match cookie.to_header(config.cookie_secret.as_deref()) {
+                Ok(cookie) => Server::push_header(&mut response_string, "Set-Cookie", &cookie),
+                Err(reason) => log_error!("Cookie dropped: {}", reason),
+            }
+        }
+        // Content-Length must describe the body even when it is not sent (HEAD), and is needed
+        // for an empty body too so a kept-alive client knows where the response ends
+        let has_body = !matches!(response.status_code, 100..=199 | 204 | 304);
+        if response.is_chunked() {
+            response_string.push_str("Transfer-Encoding: chunked\r\n");
+        } else if has_body && response.stream.is_none() && !response.has_header("Content-Length") {
+            let length = response.body.as_ref().map_or(0, |body| body.len());
+            response_string.push_str(&format!("Content-Length: {}\r\n", length));
+        }
+        if response.close && !response.has_header("Connection") {
+            response_string.push_str("Connection: close\r\n");
+        }
+        response_string.push_str("\r\n");
+
+        let mut res = response_string.into_bytes();
+        if let Some(body) = &response.body {
+            if !is_head {
+                res.extend_from_slice(body.as_bytes()); // Append the response body if it exists
             }
+        }
+
+        res
+    }
+
+    /// Appends a header line to a response head, unless the name is not a valid token or the
+    /// value holds CR, LF or NUL. Such a header is dropped and logged: written as is, a value
+    /// taken from user input could end the header early and add headers or a body of its
+    /// own (response splitting).
+    fn push_header(head: &mut String, name: &str, value: &str) {
+        if !Server::is_token(name) {
+            log_error!("Response header dropped: invalid name {:?}", name);
+        } else if value.contains(['\r', '\n', '\0']) {
+            log_error!(
+                "Response header {} dropped: line break or NUL in its value",
+                name
+            );
+        } else {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+    }
+}
+
+/// Counts a connection, or a request, as active for as long as it is alive, even if its
+/// handler panics.
+pub(crate) struct ActiveConnection(Arc<AtomicUsize>);
+
+impl ActiveConnection {
+    pub(crate) fn new(counter: &Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(Arc::clone(counter))
+    }
+}
+
+impl Drop for ActiveConnection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A handle to a server started with `Server::bind`.
+///
+/// Dropping the handle leaves the server running in the background.
+pub struct ServerHandle {
+    addrs: Vec<SocketAddr>,
+    shutdown: Arc<AtomicBool>,
+    threads: Vec<thread::JoinHandle<()>>,
+    active_connections: Arc<AtomicUsize>,
+    counters: Arc<Counters>,
+}
+
+impl ServerHandle {
+    /// Returns the addresses the server is listening on, with the actual ports when port 0
+    /// was requested.
+    pub fn local_addrs(&self) -> &[SocketAddr] {
+        &self.addrs
+    }
+
+    /// Returns the number of connections currently being handled.
+    pub fn active_connections(&self) -> usize {
+        self.active_connections.load(Ordering::SeqCst)
+    }
+
+    /// Returns the counts of connections and requests handled by the server, shared so they
+    /// can be read after the handle is gone. See `Counters`.
+    pub fn counters(&self) -> Arc<Counters> {
+        Arc::clone(&self.counters)
+    }
+
+    /// Stops accepting new connections on every listener.
+    ///
+    /// Connections already accepted finish the request in progress and are then closed.
+    pub fn shutdown(&self) {
+        ServerHandle::stop(&self.addrs, &self.shutdown);
+    }
+
+    /// Shuts the server down when the process receives SIGINT (Ctrl-C) or SIGTERM, as sent by
+    /// container runtimes and process managers.
+    ///
+    /// Registers handlers for both signals, so they no longer terminate the process directly;
+    /// the program ends once its main thread returns, usually after `drain`. Requires the
+    /// `signals` feature and a Unix platform.
+    ///
+    /// # Returns
+    /// An error if the signal handlers could not be registered.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use guest_server::{HttpResponse,Server};
+    /// use std::time::Duration;
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/", |_| HttpResponse::new(200, Some("Hello, World!".to_string())));
+    /// let handle = server.bind("0.0.0.0:8080").unwrap();
+    /// handle.handle_signals().unwrap();
+    /// // Returns after a signal, once in-flight requests are done or after 30 seconds
+    /// handle.drain(Duration::from_secs(30));
+    /// ```
+    #[cfg(feature = "signals")]
+    pub fn handle_signals(&self) -> Result<(), Error> {
+        use signal_hook::{
+            consts::{SIGINT, SIGTERM},
+            iterator::Signals,
+        };
+
+        let mut signals = Signals::new([SIGINT, SIGTERM])?;
+        let addrs = self.addrs.clone();
+        let shutdown = Arc::clone(&self.shutdown);
+        thread::spawn(move || {
+            if signals.forever().next().is_some() {
+                ServerHandle::stop(&addrs, &shutdown);
+            }
+        });
+        Ok(())
+    }
+
+    /// Waits until every accept loop has stopped, i.e. after `shutdown`, and then until the
+    /// connections in progress have finished, for at most `grace`.
+    ///
+    /// # Parameters
+    /// - 'grace' : The longest time to wait for connections once the listeners stopped.
+    ///   Connections kept alive by idle clients only finish on their own with a read timeout.
+    ///
+    /// # Returns
+    /// `true` if every connection finished in time.
+    pub fn drain(self, grace: Duration) -> bool {
+        let active_connections = Arc::clone(&self.active_connections);
+        self.join();
+        let deadline = Instant::now() + grace;
+        while active_connections.load(Ordering::SeqCst) > 0 {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        true
+    }
+
+    /// Sets the shutdown flag and wakes every accept loop so it observes it.
+    fn stop(addrs: &[SocketAddr], shutdown: &AtomicBool) {
+        shutdown.store(true, Ordering::SeqCst);
+        // Accept blocks, so wake each loop with a connection of our own to observe the flag
+        for addr in addrs {
+            let mut addr = *addr;
+            if addr.ip().is_unspecified() {
+                addr.set_ip(match addr {
+                    SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                    SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+                });
+            }
+            let _ = TcpStream::connect(addr);
+        }
+    }
+
+    /// Waits until every accept loop has stopped, i.e. after `shutdown`.
+    pub fn join(self) {
+        for thread in self.threads {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Represents an incoming HTTP request as handed to a route handler.
+#[derive(Clone)]
+pub struct Request {
+    pub method: HttpMethod,
+    /// The path of the request target, without the query string, e.g. `/home`.
+    pub path: String,
+    /// The parsed query string, e.g. `page=2` for `/home?page=2`.
+    pub query: Query,
+    /// The path parameters captured by the matched route, e.g. `id` => `42` for the route
+    /// `/users/:id` and the path `/users/42`.
+    ///
+    /// Values are percent-decoded, so `/users/john%20doe` gives `john doe`. Routes are
+    /// matched on the path as sent, so an encoded slash stays within its segment:
+    /// `/users/a%2Fb` matches `/users/:id` with `a/b`. A value that is not validly encoded
+    /// UTF-8 makes the request fail with 400 Bad Request before the handler runs. Use
+    /// `Params::get_as` to parse a value, e.g. a numeric ID.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "testing")] {
+    /// use guest_server::{Server, TestClient};
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/users/:name", |request| request.params["name"].clone());
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let client = TestClient::new(handle.local_addrs()[0]);
+    /// assert_eq!(client.get("/users/john%20doe").unwrap().text(), "john doe");
+    /// assert_eq!(client.get("/users/a%2Fb").unwrap().text(), "a/b");
+    /// assert_eq!(client.get("/users/a/b").unwrap().status_code, 404);
+    /// assert_eq!(client.get("/users/%FF").unwrap().status_code, 400);
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// # }
+    /// ```
+    pub params: Params,
+    /// The path pattern of the route that matched, e.g. `/users/:id` for `/users/42`, or
+    /// `None` if the request was served without a route, e.g. by the fallback. Unlike the
+    /// path, it takes one value per route, so it makes a good key for per-route metrics.
+    pub route: Option<String>,
+    /// The request headers, keyed by lowercase header name, with values trimmed of
+    /// surrounding whitespace.
+    ///
+    /// Requests with a malformed header are answered with 400 Bad Request before reaching a
+    /// handler: a line without a colon, a name with characters other than letters, digits
+    /// and ``!#$%&'*+-.^_`|~`` (whitespace included), or a value with control characters
+    /// other than tab, such as a CR smuggling in another header.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "testing")] {
+    /// use guest_server::{Server, TestClient};
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/", |request| request.headers["x-name"].clone());
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let client = TestClient::new(handle.local_addrs()[0]);
+    /// let send = |name: &str, value: &str| client.request("GET", "/", &[(name, value)], &[]);
+    /// assert_eq!(send("X-Name", "\t value ").unwrap().text(), "value");
+    /// assert_eq!(send("X-Name", "a\rSet-Cookie: admin=1").unwrap().status_code, 400);
+    /// assert_eq!(send("X-Name", "a\u{0}b").unwrap().status_code, 400);
+    /// assert_eq!(send("X-Name ", "value").unwrap().status_code, 400);
+    /// assert_eq!(send("X(Name)", "value").unwrap().status_code, 400);
+    /// assert_eq!(send("X-Name: a\r\n Folded", "b").unwrap().status_code, 400);
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// # }
+    /// ```
+    pub headers: HashMap<String, String>,
+    /// The cookies sent in the `Cookie` header, keyed by name. Values sent in double quotes
+    /// are stored without them.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "testing")] {
+    /// use guest_server::{Server, TestClient};
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/prefs", |request| {
+    ///     format!("{} {}", request.cookies["theme"], request.cookies["lang"])
+    /// });
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let client = TestClient::new(handle.local_addrs()[0]);
+    /// let cookie = [("Cookie", "theme=\"dark\"; lang=en")];
+    /// let response = client.request("GET", "/prefs", &cookie, b"").unwrap();
+    /// assert_eq!(response.text(), "dark en");
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// # }
+    /// ```
+    pub cookies: HashMap<String, String>,
+    /// The signed cookies whose signature checked out against the server's cookie secret,
+    /// keyed by name and holding the original value. Tampered cookies are left out.
+    pub signed_cookies: HashMap<String, String>,
+    /// The client's session, saved after the handler runs when sessions are enabled.
+    pub session: Session,
+    /// The request body, as delimited by `Content-Length`; empty without one.
+    ///
+    /// A `Content-Length` that is not a plain number, or that is repeated with different
+    /// values, is answered with 400 Bad Request and the connection is closed, as the end of
+    /// the body, and so the start of the next request, cannot be told. Bodies sent with a
+    /// `Transfer-Encoding`, e.g. chunked, are not decoded; such a request is answered with
+    /// 501 Not Implemented and the connection closed the same way.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use guest_server::Server;
+    /// use std::io::{Read, Write};
+    /// use std::net::TcpStream;
+    ///
+    /// let mut server = Server::new();
+    /// server.post("/echo", |request| request.body.clone());
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let send = |request: &str| {
+    ///     let mut stream = TcpStream::connect(handle.local_addrs()[0]).unwrap();
+    ///     stream.write_all(request.as_bytes()).unwrap();
+    ///     let mut response = String::new();
+    ///     stream.read_to_string(&mut response).unwrap();
+    ///     response
+    /// };
+    /// let next = "GET /next HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n";
+    /// let ok = send(&format!(
+    ///     "POST /echo HTTP/1.1\r\nHost: test\r\nContent-Length: 2\r\n\r\nhi{}",
+    ///     next
+    /// ));
+    /// assert!(ok.starts_with("HTTP/1.1 200 OK\r\n"));
+    /// assert!(ok.contains("\r\n\r\nhiHTTP/1.1 404 "));
+    ///
+    /// let conflicting = "Content-Length: 2\r\nContent-Length: 3";
+    /// for lengths in ["Content-Length: +2", "Content-Length: 2, 2", conflicting] {
+    ///     let response = send(&format!(
+    ///         "POST /echo HTTP/1.1\r\nHost: test\r\n{}\r\n\r\nhi{}",
+    ///         lengths, next
+    ///     ));
+    ///     assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    ///     assert!(response.contains("Connection: close\r\n"));
+    ///     assert!(!response.contains("404"));
+    /// }
+    ///
+    /// let head = "POST /echo HTTP/1.1\r\nHost: test\r\nTransfer-Encoding: chunked\r\n\r\n";
+    /// let chunked = send(&format!("{}{:x}\r\n{}\r\n0\r\n\r\n", head, next.len(), next));
+    /// assert!(chunked.starts_with("HTTP/1.1 501 Not Implemented\r\n"));
+    /// assert!(chunked.contains("Connection: close\r\n"));
+    /// assert!(!chunked.contains("404"));
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// ```
+    pub body: Vec<u8>,
+    /// The protocol version from the request line, e.g. `HTTP/1.1`.
+    ///
+    /// Always `HTTP/1.0` or `HTTP/1.1` (or another HTTP/1 minor version); requests of other
+    /// major versions are answered with 505 HTTP Version Not Supported, and malformed versions
+    /// with 400, before any handler runs. A connection opening with the HTTP/2 preface
+    /// (`PRI * HTTP/2.0`), as sent by clients trying HTTP/2 in the clear, is answered with an
+    /// HTTP/2 GOAWAY frame asking for HTTP/1.1 and closed.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use guest_server::Server;
+    /// use std::io::{Read, Write};
+    /// use std::net::TcpStream;
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/", |request| request.version.clone());
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let send = |request: &str| {
+    ///     let mut stream = TcpStream::connect(handle.local_addrs()[0]).unwrap();
+    ///     stream.write_all(request.as_bytes()).unwrap();
+    ///     let mut response = String::new();
+    ///     stream.read_to_string(&mut response).unwrap();
+    ///     response
+    /// };
+    /// assert!(send("GET / HTTP/2.0\r\nHost: test\r\n\r\n")
+    ///     .starts_with("HTTP/1.1 505 HTTP Version Not Supported\r\n"));
+    /// assert!(send("GET / HTTP/one\r\nHost: test\r\n\r\n").starts_with("HTTP/1.1 400 "));
+    /// assert!(send("GET / HTTP/1.0\r\n\r\n").ends_with("\r\n\r\nHTTP/1.0"));
+    ///
+    /// let refusal = send("PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n").into_bytes();
+    /// assert_eq!(refusal.len(), 26);
+    /// assert_eq!(refusal[3], 0x4); // SETTINGS
+    /// assert_eq!(refusal[12], 0x7); // GOAWAY
+    /// assert_eq!(refusal[25], 0xd); // HTTP_1_1_REQUIRED
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// ```
+    pub version: String,
+    /// Identifies the request across logs and services. Taken from the incoming `X-Request-Id`
+    /// header when present, generated otherwise, and echoed back in the response.
+    pub request_id: String,
+    /// When the server began reading the request, for handlers measuring their own latency,
+    /// e.g. for a `Server-Timing` header.
+    pub start: Instant,
+    interim: Interim, // Where `early_hints` writes, while the final response is not yet sent.
+}
+
+impl Request {
+    /// Returns the value of a request header, matching its name case-insensitively.
+    ///
+    /// # Parameters
+    /// - 'name' : The header name in any case, e.g. 'Authorization'.
+    ///
+    /// # Returns
+    /// The header value, or `None` if the client did not send the header. Of a repeated
+    /// header, the last value is kept.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use guest_server::{HttpResponse,Server};
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/private", |request| match request.header("Authorization") {
+    ///     Some("Bearer secret") => HttpResponse::new(200, None),
+    ///     _ => HttpResponse::new(401, None),
+    /// });
+    /// server.listener(8080);
+    /// ```
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    /// Returns the length of the request body, as sent in `Content-Length`, or 0 without one.
+    ///
+    /// The header was checked to be a plain number when the request was parsed.
+    pub(crate) fn content_length(&self) -> u64 {
+        self.headers
+            .get("content-length")
+            .and_then(|length| length.parse().ok())
+            .unwrap_or(0)
+    }
+
+    /// Returns the entity tags of the `If-Match` header, e.g. `"v2"` or `W/"v2"` with their
+    /// quotes, or `*` for any.
+    ///
+    /// # Returns
+    /// The tags in the order sent, or `None` if the client did not send the header.
+    pub fn if_match(&self) -> Option<Vec<&str>> {
+        self.header("if-match").map(Request::entity_tags)
+    }
+
+    /// Returns the entity tags of the `If-None-Match` header, as for `if_match`.
+    pub fn if_none_match(&self) -> Option<Vec<&str>> {
+        self.header("if-none-match").map(Request::entity_tags)
+    }
 
-            if line.to_lowercase().starts_with("content-length:") {
-                if let Ok(length) = line["content-length:".len()..].trim().parse::<usize>() {
-                    content_length = length;
+    /// Evaluates the `If-Match` and `If-None-Match` preconditions of the request against the
+    /// current entity tag of the resource it targets, e.g. before applying an update with
+    /// optimistic locking.
+    ///
+    /// `If-Match` passes if it lists the current tag, compared strongly, so weak tags never
+    /// match, or is `*` and the resource exists. `If-None-Match` passes unless it lists the
+    /// current tag, compared weakly, or is `*` and the resource exists. A request without
+    /// these headers passes.
+    ///
+    /// # Parameters
+    /// - 'etag' : The current entity tag with its quotes, e.g. `"v2"`, or `None` if the
+    ///   resource does not exist.
+    ///
+    /// # Returns
+    /// `None` if the handler should go ahead, or the response to send instead: 412
+    /// Precondition Failed, or for a GET or HEAD request failing `If-None-Match`, 304 Not
+    /// Modified with the `ETag`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "testing")] {
+    /// use guest_server::{HttpResponse, Server, TestClient};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let version = Arc::new(Mutex::new(1));
+    /// let mut server = Server::new();
+    /// let current = Arc::clone(&version);
+    /// server.get("/doc", move |request| {
+    ///     let etag = format!("\"{}\"", current.lock().unwrap());
+    ///     if let Some(response) = request.check_preconditions(Some(&etag)) {
+    ///         return response;
+    ///     }
+    ///     HttpResponse::status(200).insert_header("ETag", &etag)
+    /// });
+    /// server.patch("/doc", move |request| {
+    ///     let mut version = version.lock().unwrap();
+    ///     if let Some(response) = request.check_preconditions(Some(&format!("\"{}\"", version))) {
+    ///         return response;
+    ///     }
+    ///     *version += 1;
+    ///     HttpResponse::status(204)
+    /// });
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let client = TestClient::new(handle.local_addrs()[0]);
+    /// let update = |etag| client.request("PATCH", "/doc", &[("If-Match", etag)], b"{}");
+    /// assert_eq!(update("\"1\"").unwrap().status_code, 204);
+    /// // Someone else's update, based on the version before
+    /// assert_eq!(update("\"1\"").unwrap().status_code, 412);
+    ///
+    /// let cached = client.request("GET", "/doc", &[("If-None-Match", "\"2\"")], b"");
+    /// assert_eq!(cached.unwrap().status_code, 304);
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// # }
+    /// ```
+    pub fn check_preconditions(&self, etag: Option<&str>) -> Option<HttpResponse> {
+        let listed = |tags: &[&str], matches: fn(&str, &str) -> bool| match etag {
+            Some(etag) => tags.iter().any(|tag| *tag == "*" || matches(tag, etag)),
+            None => false,
+        };
+        if let Some(tags) = self.if_match() {
+            if !listed(&tags, Request::strong_match) {
+                return Some(HttpResponse::status(412));
+            }
+        }
+        if let Some(tags) = self.if_none_match() {
+            if listed(&tags, Request::weak_match) {
+                if !matches!(self.method, HttpMethod::GET | HttpMethod::HEAD) {
+                    return Some(HttpResponse::status(412));
                 }
+                let mut response = HttpResponse::status(304);
+                response.headers.remove("Content-Type"); // There is no body to describe.
+                return Some(response.insert_header("ETag", etag.unwrap_or_default()));
+            }
+        }
+        None
+    }
+
+    /// Splits a list of entity tags, as sent in `If-Match` or `If-None-Match`.
+    fn entity_tags(value: &str) -> Vec<&str> {
+        let mut tags = Vec::new();
+        let mut rest = value;
+        loop {
+            rest = rest.trim_start_matches([' ', '\t', ',']);
+            if rest.is_empty() {
+                return tags;
             }
+            let opaque = rest.strip_prefix("W/").unwrap_or(rest);
+            let end = match opaque.strip_prefix('"') {
+                // The quoted part of a tag may itself contain commas
+                Some(quoted) => {
+                    let start = rest.len() - quoted.len();
+                    quoted.find('"').map_or(rest.len(), |i| start + i + 1)
+                }
+                None => rest.find(',').unwrap_or(rest.len()),
+            };
+            tags.push(rest[..end].trim_end());
+            rest = &rest[end..];
+        }
+    }
+
+    /// Compares entity tags strongly: both must be strong and identical.
+    fn strong_match(a: &str, b: &str) -> bool {
+        !a.starts_with("W/") && a == b
+    }
+
+    /// Compares entity tags weakly: identical once any weak prefix is removed.
+    fn weak_match(a: &str, b: &str) -> bool {
+        a.strip_prefix("W/").unwrap_or(a) == b.strip_prefix("W/").unwrap_or(b)
+    }
+
+    /// Sends a `103 Early Hints` interim response with `Link` headers ahead of the final
+    /// response, so the browser can start preloading or preconnecting while the handler
+    /// is still at work.
+    ///
+    /// Hints are only sent to HTTP/1.1 clients of the thread-per-connection server, and only
+    /// while the handler runs; otherwise, e.g. to an HTTP/1.0 client, nothing is sent. The
+    /// final response must repeat any links that should still apply to it.
+    ///
+    /// # Parameters
+    /// - 'links' : The `Link` header values, e.g. `</style.css>; rel=preload; as=style`.
+    ///
+    /// # Returns
+    /// An error of kind `InvalidInput` if a link holds a CR, LF or NUL, which could split the
+    /// response, or the error writing to the connection.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use guest_server::Server;
+    /// use std::io::{Read, Write};
+    /// use std::net::TcpStream;
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/", |request| {
+    ///     let _ = request.early_hints(&["</style.css>; rel=preload; as=style"]);
+    ///     "<html>...</html>" // Rendered while the browser fetches the stylesheet.
+    /// });
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let mut stream = TcpStream::connect(handle.local_addrs()[0]).unwrap();
+    /// stream.write_all(b"GET / HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n").unwrap();
+    /// let mut responses = String::new();
+    /// stream.read_to_string(&mut responses).unwrap();
+    /// assert!(responses.starts_with(
+    ///     "HTTP/1.1 103 Early Hints\r\nLink: </style.css>; rel=preload; as=style\r\n\r\nHTTP/1.1 200 OK\r\n"
+    /// ));
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// ```
+    pub fn early_hints(&self, links: &[&str]) -> Result<(), Error> {
+        if links.iter().any(|link| link.contains(['\r', '\n', '\0'])) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Link holds CR, LF or NUL",
+            ));
+        }
+        let mut head = String::from("HTTP/1.1 103 Early Hints\r\n");
+        for link in links {
+            head.push_str(&format!("Link: {}\r\n", link));
+        }
+        head.push_str("\r\n");
+        self.interim.write(head.as_bytes())
+    }
+}
+
+/// The read side of a connection, holding each request to `Server::request_deadline`.
+pub(crate) struct RequestReader {
+    stream: TcpStream,
+    read_timeout: Option<Duration>, // The timeout of a single read, as set on the socket.
+    deadline: Option<Instant>,      // When the request being read must be complete.
+}
+
+impl RequestReader {
+    fn new(stream: TcpStream, config: &Config) -> Self {
+        Self {
+            stream,
+            read_timeout: config.read_timeout,
+            deadline: None,
         }
+    }
 
-        if !header_parsed {
-            return Err(Error::new(ErrorKind::InvalidData, "Incomplete header"));
+    /// Lifts the deadline once the request is read, restoring the socket's read timeout.
+    fn end_deadline(&mut self) -> Result<(), Error> {
+        if self.deadline.take().is_some() {
+            self.stream.set_read_timeout(self.read_timeout)?;
         }
+        Ok(())
+    }
 
-        let mut body = Vec::new();
-        if content_length > 0 {
-            body.resize(content_length, 0);
-            reader.read_exact(&mut body)?;
-        }
+    /// Returns the connection, e.g. to tunnel it.
+    pub(crate) fn into_inner(self) -> TcpStream {
+        self.stream
+    }
+}
 
-        buffer_request.extend_from_slice(&body);
+impl Read for RequestReader {
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        if let Some(deadline) = self.deadline {
+            // No single read may outlast the deadline
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(Error::new(ErrorKind::TimedOut, "Request deadline passed"));
+            }
+            let timeout = self.read_timeout.map_or(remaining, |t| t.min(remaining));
+            self.stream.set_read_timeout(Some(timeout))?;
+        }
+        self.stream.read(buffer)
+    }
+}
 
-        let response = if let Some(method) = method {
-            Server::processing_response(&routes, body, method, path)
-        } else {
-            HttpResponse::new(405, None)
-        };
+/// A request arriving in pieces on the servers that buffer it before parsing: its head, once
+/// complete, is parsed a single time, and the body then waits until all of it has arrived.
+#[cfg(any(feature = "event-loop", feature = "tokio"))]
+#[derive(Default)]
+pub(crate) struct PartialRequest {
+    head: Option<(Request, usize)>, // The parsed head and its length in bytes.
+}
 
-        let res = Server::generate_http_response(&response);
-        Server::send_response(&mut stream, res);
+#[cfg(any(feature = "event-loop", feature = "tokio"))]
+impl PartialRequest {
+    /// Room for the headers beyond the request line, past which the head is refused with 431.
+    const MAX_HEADERS: usize = 64 * 1024;
 
-        Ok(())
+    /// Returns how many bytes the receive buffer should hold before parsing again: the whole
+    /// request once its head is parsed, and one byte past the largest head accepted before.
+    pub(crate) fn wanted(&self, config: &Config) -> usize {
+        match &self.head {
+            Some((request, length)) => length + request.content_length() as usize,
+            None => config.max_request_line + PartialRequest::MAX_HEADERS + 1,
+        }
     }
 
-    /// Processes the HTTP response based on the method and path, invoking the registered handler.
+    /// Parses the request as far as it has been received.
     ///
     /// # Parameters
-    /// - 'routes' : A shared reference to the routes configuration.
-    /// - 'body' : The body of the request as a vector of bytes.
-    /// - 'method' : The HTTP method (GET, POST) for the request.
-    /// - 'path' : The requested path for the route.
+    /// - 'received' : Everything received on the connection so far.
+    /// - 'config' : The server-wide settings holding the request limits.
     ///
     /// # Returns
-    /// The generated HttpResponse based on the handler or a 404 response if no handler is found.
-
-    fn processing_response(
-        routes: &Routes,
-        body: Vec<u8>,
-        method: HttpMethod,
-        path: String,
-    ) -> HttpResponse {
-        routes
-            .read()
-            .unwrap()
-            .get(&(method, path))
-            .cloned()
-            .map_or_else(
-                || HttpResponse::new(404, None),
-                |handler| handler(Some(body)),
-            )
-    }
+    /// The request once complete, `None` while more of it has to arrive, or the `ParseError`
+    /// of `parse_head` and `read_body`. A body over `max_body` is refused with 413 as soon as
+    /// the head announcing it is parsed, and a head that does not end within the limit with 431.
+    pub(crate) fn parse(
+        &mut self,
+        received: &[u8],
+        config: &Config,
+    ) -> Result<Option<Request>, ParseError> {
+        if self.head.is_none() {
+            let limit = config.max_request_line + PartialRequest::MAX_HEADERS;
+            let complete = received.windows(4).any(|window| window == b"\r\n\r\n");
+            if !complete && received.len() <= limit {
+                return Ok(None);
+            }
+            let mut rest = received;
+            let request = match Server::parse_head(&mut rest, config) {
+                Ok(request) => request,
+                Err(ParseError::Io(e)) if e.kind() == ErrorKind::UnexpectedEof => {
+                    log_warn!("Rejected request with a head over {} bytes", limit);
+                    return Err(ParseError::Status(431));
+                }
+                Err(e) => return Err(e),
+            };
+            if request.content_length() > config.max_body as u64 {
+                log_warn!("Refused request body of {} bytes", request.content_length());
+                return Err(ParseError::Status(413));
+            }
+            self.head = Some((request, received.len() - rest.len()));
+        }
 
-    /// Sends an HTTP response to the client.
-    ///
-    /// # Parameters
-    /// - 'stream' : The TCP stream to send the response over.
-    /// - 'response' : The response content (HTTP status, headers, body) to be sent.
-    ///
-    /// # Notes
-    /// This function writes the full HTTP response to the provided stream.
-    /// It logs an error if the response cannot be sent.
-    fn send_response(stream: &mut TcpStream, response: Vec<u8>) {
-        if let Err(e) = stream.write_all(&response) {
-            eprintln!("Failed to send response: {}", e);
+        let end = self.wanted(config);
+        match self.head.take() {
+            Some((mut request, length)) if received.len() >= end => {
+                Server::read_body(&mut &received[length..end], &mut request, config)?;
+                Ok(Some(request))
+            }
+            head => {
+                self.head = head;
+                Ok(None)
+            }
         }
     }
+}
 
-    /// Generates the full HTTP response string, including status code, headers, and body.
-    ///
-    /// # Parameters
-    /// - 'response' : The HttpResponse object containing status, headers, and body.
-    ///
-    /// # Returns
-    /// A vector of bytes representing the full HTTP response.
-    fn generate_http_response(response: &HttpResponse) -> Vec<u8> {
-        let mut response_string = format!(
-            "HTTP/1.1 {} {}\r\n",
-            response.status_code,
-            response.get_status_message() // Retrieves the status message based on status code
-        );
-        for (key, value) in &response.headers {
-            response_string.push_str(&format!("{}: {}\r\n", key, value)); // Add headers to the response
-        }
-        response_string.push_str("\r\n");
+/// The write side of a connection, lent to a handler for interim (1xx) responses until the
+/// final response is about to be sent.
+#[derive(Clone, Default)]
+struct Interim(Arc<Mutex<Option<Arc<TcpStream>>>>);
 
-        let mut res = response_string.into_bytes();
-        if let Some(body) = &response.body {
-            res.extend_from_slice(body.as_bytes()); // Append the response body if it exists
+impl Interim {
+    fn new(stream: &Arc<TcpStream>) -> Self {
+        Self(Arc::new(Mutex::new(Some(Arc::clone(stream)))))
+    }
+
+    /// Writes an interim response, or nothing once the connection is no longer lent.
+    fn write(&self, head: &[u8]) -> Result<(), Error> {
+        match &*self.0.lock().unwrap_or_else(PoisonError::into_inner) {
+            Some(stream) => (&**stream).write_all(head),
+            None => Ok(()),
         }
+    }
 
-        res
+    /// Takes the connection back, waiting for an interim response being written to finish.
+    fn close(&self) {
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner) = None;
     }
 }
 
+/// A response rendered for sending.
+struct Outgoing {
+    bytes: Vec<u8>,                       // Status line, headers and any fixed body.
+    stream: Option<Box<dyn Read + Send>>, // The streamed body still to be sent, if any.
+    chunked: bool,                        // Whether the streamed body is sent in chunks.
+    trailers: Option<Trailers>,           // Sent after the last chunk of a chunked body.
+    keep_alive: bool,                     // Whether the connection stays open afterwards.
+}
+
 /// Represents an HTTP response, including status code, headers, and body.
 pub struct HttpResponse {
     pub status_code: u16,
     pub headers: HashMap<String, String>,
-    pub body: Option<String>,
+    /// The body, borrowed when it is a constant so sending it allocates nothing.
+    pub body: Option<Cow<'static, str>>,
+    /// Cookies to set on the client, each sent as a `Set-Cookie` header.
+    pub cookies: Vec<Cookie>,
+    close: bool, // Whether to close the connection after this response.
+    stream: Option<Box<dyn Read + Send>>, // A body of unknown length, sent as it is read.
+    implicit_type: bool, // Whether `Content-Type` is the `text/plain` default of `new`.
+    deferred: Option<Box<(Receiver<HttpResponse>, Duration)>>, // Where the actual response comes from.
+    reason: Option<String>, // Replaces the standard reason phrase of the status code.
+    trailers: Option<Trailers>, // Computes the fields sent after a chunked body.
 }
 
 impl HttpResponse {
     /// Creates a new HttpResponse with the specified status code and body.
     ///
+    /// The Content-Type is `text/plain` whatever the body contains, unless the server
+    /// changes that default with `Server::default_content_type`; use
+    /// `HttpResponse::with_content_type` for any other type.
+    ///
     /// # Parameters
     /// - 'status_code' : The HTTP status code (e.g., 200, 404).
     /// - 'body' : The response body content (optional).
     pub fn new(status_code: u16, body: Option<String>) -> Self {
+        let mut response = HttpResponse::with_content_type(status_code, "text/plain", body);
+        response.implicit_type = true;
+        response
+    }
+
+    /// Creates a response with a status code and no body, e.g. for errors rendered by
+    /// `Server::error_body`, same as `HttpResponse::new(status_code, None)`.
+    ///
+    /// # Parameters
+    /// - 'status_code' : The HTTP status code (e.g., 204, 404).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use guest_server::{HttpResponse, IntoResponse, Server};
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/admin", |_| HttpResponse::status(403));
+    /// server.get("/legacy", |_| 410);
+    ///
+    /// let response = 404.into_response();
+    /// assert_eq!(response.status_code, 404);
+    /// assert!(response.body.is_none());
+    /// ```
+    pub fn status(status_code: u16) -> Self {
+        HttpResponse::new(status_code, None)
+    }
+
+    /// Creates a new HttpResponse with the specified status code, Content-Type and body.
+    ///
+    /// # Parameters
+    /// - 'status_code' : The HTTP status code (e.g., 200, 404).
+    /// - 'content_type' : The media type of the body, e.g. 'application/json'.
+    /// - 'body' : The response body content (optional).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use guest_server::HttpResponse;
+    ///
+    /// let response = HttpResponse::with_content_type(
+    ///     200,
+    ///     "application/json",
+    ///     Some("{\"key\":\"value\"}".to_string()),
+    /// );
+    /// assert_eq!(response.headers["Content-Type"], "application/json");
+    /// ```
+    pub fn with_content_type(status_code: u16, content_type: &str, body: Option<String>) -> Self {
         let mut headers = HashMap::new();
-        let default_content_type = if let Some(ref b) = body {
-            if b.starts_with('{') && b.ends_with('}') {
-                "application/json".to_string()
-            } else {
-                "text/plain".to_string()
-            }
-        } else {
-            "text/plain".to_string()
-        };
-        headers.insert("Content-Type".to_string(), default_content_type);
+        headers.insert("Content-Type".to_string(), content_type.to_string());
         if let Some(ref b) = body {
             headers.insert("Content-Length".to_string(), b.len().to_string());
         }
@@ -316,10 +4603,146 @@ impl HttpResponse {
         HttpResponse {
             status_code,
             headers,
-            body,
+            body: body.map(Cow::Owned),
+            cookies: Vec::new(),
+            close: false,
+            stream: None,
+            implicit_type: false,
+            deferred: None,
+            reason: None,
+            trailers: None,
         }
     }
 
+    /// Creates a `text/plain` response whose body is a string constant, borrowed rather than
+    /// copied, so building and sending it allocates nothing for the body.
+    ///
+    /// # Parameters
+    /// - 'status_code' : The HTTP status code (e.g., 200, 404).
+    /// - 'body' : The response body content.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use guest_server::{HttpResponse, Server};
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/health", |_| HttpResponse::from_static(200, "ok"));
+    /// server.get("/hello", |request| {
+    ///     let name = request.query.get("name").unwrap_or("world");
+    ///     HttpResponse::from_string(200, format!("Hello, {}!", name))
+    /// });
+    /// ```
+    ///
+    /// `Content-Length` is computed when the response is sent, so the body can still be
+    /// replaced:
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "testing")] {
+    /// use guest_server::{HttpResponse, Server, TestClient};
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/", |_| {
+    ///     let mut response = HttpResponse::from_static(200, "ok");
+    ///     response.body = Some("replaced".into());
+    ///     response
+    /// });
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let response = TestClient::new(handle.local_addrs()[0]).get("/").unwrap();
+    /// assert_eq!(response.headers["content-length"], "8");
+    /// assert_eq!(response.text(), "replaced");
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// # }
+    /// ```
+    pub fn from_static(status_code: u16, body: &'static str) -> Self {
+        HttpResponse::with_body(status_code, Cow::Borrowed(body))
+    }
+
+    /// Creates a `text/plain` response whose body is an owned string, e.g. one built for the
+    /// request; the same as `HttpResponse::new` with `Some(body)`.
+    ///
+    /// # Parameters
+    /// - 'status_code' : The HTTP status code (e.g., 200, 404).
+    /// - 'body' : The response body content.
+    pub fn from_string(status_code: u16, body: String) -> Self {
+        HttpResponse::with_body(status_code, Cow::Owned(body))
+    }
+
+    /// Creates a `text/plain` response with a borrowed or owned body.
+    fn with_body(status_code: u16, body: Cow<'static, str>) -> Self {
+        // No `Content-Length` here: it is computed when sending, so the body can still change
+        let mut response = HttpResponse::new(status_code, None);
+        response.body = Some(body);
+        response
+    }
+
+    /// Creates a redirect response with an empty body.
+    ///
+    /// # Parameters
+    /// - 'status_code' : The redirect status: 301 or 308 for permanent redirects, 302, 303 or
+    ///   307 for temporary ones.
+    /// - 'location' : The URL to redirect to, sent as the `Location` header.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use guest_server::HttpResponse;
+    ///
+    /// let response = HttpResponse::redirect(301, "https://example.com/");
+    /// assert_eq!(response.headers["Location"], "https://example.com/");
+    /// ```
+    pub fn redirect(status_code: u16, location: &str) -> Self {
+        HttpResponse::new(status_code, None).insert_header("Location", location)
+    }
+
+    /// Creates an error response with an RFC 7807 problem details body, sent as
+    /// `application/problem+json`.
+    ///
+    /// The body has the members `type` (always `about:blank`), `title`, `status` and
+    /// `detail`. Handlers returning a `Result` can use it as their error type, so every
+    /// failure reaches the client in the same shape.
+    ///
+    /// # Parameters
+    /// - 'status_code' : The HTTP status code, repeated as the `status` member.
+    /// - 'title' : A short summary of the problem type, e.g. 'Out of credit'.
+    /// - 'detail' : An explanation specific to this occurrence of the problem.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use guest_server::{HttpResponse, Request, Server};
+    ///
+    /// fn withdraw(request: &Request) -> Result<String, HttpResponse> {
+    ///     let body = String::from_utf8_lossy(&request.body);
+    ///     let amount: u64 = body.trim().parse().map_err(|_| {
+    ///         HttpResponse::problem(400, "Invalid amount", "The amount must be a whole number.")
+    ///     })?;
+    ///     Ok(format!("Withdrew {}", amount))
+    /// }
+    ///
+    /// let mut server = Server::new();
+    /// server.post("/withdraw", withdraw);
+    ///
+    /// let response = HttpResponse::problem(403, "Out of credit", "Your balance is 30, but that costs 50.");
+    /// assert_eq!(response.headers["Content-Type"], "application/problem+json");
+    /// assert_eq!(
+    ///     response.body.unwrap(),
+    ///     r#"{"type":"about:blank","title":"Out of credit","status":403,"detail":"Your balance is 30, but that costs 50."}"#
+    /// );
+    /// ```
+    pub fn problem(status_code: u16, title: &str, detail: &str) -> Self {
+        let body = format!(
+            "{{\"type\":\"about:blank\",\"title\":{},\"status\":{},\"detail\":{}}}",
+            response::json_string(title),
+            status_code,
+            response::json_string(detail)
+        );
+        HttpResponse::with_content_type(status_code, "application/problem+json", Some(body))
+    }
+
     /// Adds Cross-Origin Resource Sharing (CORS) headers to the `HttpResponse`.
     ///
     /// This method sets the `Access-Control-Allow-Origin` header to `*`, allowing
@@ -329,6 +4752,8 @@ impl HttpResponse {
     /// # Usage
     ///
     /// ```rust
+    /// use guest_server::HttpResponse;
+    ///
     /// let response = HttpResponse::new(200, Some("Hello, World!".to_string())).cors();
     /// ```
     ///
@@ -342,14 +4767,312 @@ impl HttpResponse {
 
     /// Adds or updates a single header field.
     ///
+    /// A header whose name is not a valid token, or whose value contains CR, LF or NUL, is
+    /// not sent, and an error is logged, so user input placed in a value cannot add headers
+    /// or a body of its own.
+    ///
     /// # Parameters
     /// - 'key' : The header key.
     /// - 'value' : The header value.
     pub fn insert_header(mut self, key: &str, value: &str) -> Self {
+        if key.eq_ignore_ascii_case("Content-Type") {
+            self.implicit_type = false;
+        }
         self.headers.insert(key.to_string(), value.to_string());
         self
     }
 
+    /// Adds a metric to the `Server-Timing` header, which browser developer tools show
+    /// alongside the request's own timings.
+    ///
+    /// Metrics added one after another are listed in the same header, in order.
+    ///
+    /// # Parameters
+    /// - 'name' : The name of the metric, e.g. `db`. It must be an HTTP token: no spaces,
+    ///   commas, semicolons or quotes.
+    /// - 'duration' : The time spent, sent in milliseconds.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use guest_server::HttpResponse;
+    /// use std::time::Duration;
+    ///
+    /// let response = HttpResponse::new(200, None)
+    ///     .server_timing("db", Duration::from_micros(53_200))
+    ///     .server_timing("render", Duration::from_millis(4));
+    /// assert_eq!(response.headers["Server-Timing"], "db;dur=53.2, render;dur=4");
+    /// ```
+    ///
+    /// Combined with `Request::start`, a handler can report its total time:
+    ///
+    /// ```rust,no_run
+    /// use guest_server::{HttpResponse,Server};
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/", |request| {
+    ///     HttpResponse::new(200, None).server_timing("app", request.start.elapsed())
+    /// });
+    /// server.listener(8080);
+    /// ```
+    pub fn server_timing(mut self, name: &str, duration: Duration) -> Self {
+        let metric = format!("{};dur={}", name, duration.as_micros() as f64 / 1000.0);
+        let existing = self
+            .headers
+            .keys()
+            .find(|key| key.eq_ignore_ascii_case("Server-Timing"))
+            .cloned();
+        match existing.and_then(|key| self.headers.get_mut(&key)) {
+            Some(value) => {
+                value.push_str(", ");
+                value.push_str(&metric);
+            }
+            None => {
+                self.headers.insert("Server-Timing".to_string(), metric);
+            }
+        }
+        self
+    }
+
+    /// Creates a response whose body is read from `reader` while it is sent, such as the output
+    /// of a child process or a network stream, without knowing its length up front.
+    ///
+    /// The body is sent with chunked transfer encoding, or until the connection closes if it
+    /// will not be kept alive (e.g. for HTTP/1.0 clients). When the length is known after all,
+    /// set it with `insert_header("Content-Length", ...)` to send the body unframed. If reading
+    /// fails midway the connection is aborted, so the client sees an incomplete response
+    /// rather than a short one. The Content-Type is `application/octet-stream` unless set with
+    /// `insert_header`.
+    ///
+    /// # Parameters
+    /// - 'status_code' : The HTTP status code (e.g., 200, 404).
+    /// - 'reader' : The source of the body, read until its end.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use guest_server::{HttpResponse,Server};
+    /// use std::process::{Command, Stdio};
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/uptime", |_| {
+    ///     match Command::new("uptime").stdout(Stdio::piped()).spawn() {
+    ///         Ok(child) => HttpResponse::stream(200, child.stdout.unwrap())
+    ///             .insert_header("Content-Type", "text/plain"),
+    ///         Err(_) => HttpResponse::new(500, None),
+    ///     }
+    /// });
+    /// server.listener(8080);
+    /// ```
+    pub fn stream<R: Read + Send + 'static>(status_code: u16, reader: R) -> Self {
+        let mut response =
+            HttpResponse::with_content_type(status_code, "application/octet-stream", None);
+        response.stream = Some(Box::new(reader));
+        response
+    }
+
+    /// Creates a response whose body is generated piece by piece by an iterator, such as the
+    /// rows of a CSV export, without holding it whole in memory.
+    ///
+    /// Each piece is sent as it is yielded, framed like the body of `HttpResponse::stream`,
+    /// with chunked transfer encoding on connections that stay open; empty pieces are skipped.
+    /// The iterator runs on the connection's thread while the response is written. If it
+    /// panics, the connection is aborted without the terminating chunk, so the client sees an
+    /// incomplete response rather than a short one. The Content-Type is
+    /// `application/octet-stream` unless set with `insert_header`.
+    ///
+    /// # Parameters
+    /// - 'status_code' : The HTTP status code (e.g., 200, 404).
+    /// - 'chunks' : The pieces of the body, in order.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "testing")] {
+    /// use guest_server::{HttpResponse, Server, TestClient};
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/export.csv", |_| {
+    ///     let header = std::iter::once(b"id,square\n".to_vec());
+    ///     let rows = (1..=3).map(|i: u64| format!("{},{}\n", i, i * i).into_bytes());
+    ///     HttpResponse::chunks(200, header.chain(rows)).insert_header("Content-Type", "text/csv")
+    /// });
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let response = TestClient::new(handle.local_addrs()[0]).get("/export.csv").unwrap();
+    /// assert_eq!(response.text(), "id,square\n1,1\n2,4\n3,9\n");
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// # }
+    /// ```
+    pub fn chunks<I>(status_code: u16, chunks: I) -> Self
+    where
+        I: IntoIterator<Item = Vec<u8>>,
+        I::IntoIter: Send + 'static,
+    {
+        HttpResponse::stream(status_code, response::ChunkReader::new(chunks.into_iter()))
+    }
+
+    /// Creates a response that is computed elsewhere, e.g. on a worker thread or by whatever
+    /// answers a queued job, and sent once it arrives on `receiver`.
+    ///
+    /// The handler returns straight away; the connection waits for the response, up to
+    /// `timeout`. If none arrives in time the client gets 503, and if the sending side is
+    /// dropped without sending, 500. The response sent may itself be deferred.
+    /// The event-loop server waits on its loop thread, holding up its other connections; the
+    /// async server does not support deferred responses, as its handlers can await instead.
+    ///
+    /// # Parameters
+    /// - 'receiver' : Where the response will be sent, e.g. by a thread holding the `Sender`.
+    /// - 'timeout' : How long to wait for it.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "testing")] {
+    /// use guest_server::{HttpResponse, Server, TestClient};
+    /// use std::sync::mpsc;
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/report", |_| {
+    ///     let (sender, receiver) = mpsc::channel();
+    ///     thread::spawn(move || {
+    ///         thread::sleep(Duration::from_millis(50)); // Waiting on a slow backend.
+    ///         let _ = sender.send(HttpResponse::from_static(200, "report ready"));
+    ///     });
+    ///     HttpResponse::deferred(receiver, Duration::from_secs(5))
+    /// });
+    /// server.get("/stuck", |_| {
+    ///     let (sender, receiver) = mpsc::channel::<HttpResponse>();
+    ///     thread::spawn(move || {
+    ///         thread::sleep(Duration::from_secs(1));
+    ///         drop(sender);
+    ///     });
+    ///     HttpResponse::deferred(receiver, Duration::from_millis(50))
+    /// });
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let client = TestClient::new(handle.local_addrs()[0]);
+    /// assert_eq!(client.get("/report").unwrap().text(), "report ready");
+    /// assert_eq!(client.get("/stuck").unwrap().status_code, 503);
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// # }
+    /// ```
+    pub fn deferred(receiver: Receiver<HttpResponse>, timeout: Duration) -> Self {
+        let mut response = HttpResponse::status(500);
+        response.deferred = Some(Box::new((receiver, timeout)));
+        response
+    }
+
+    /// Waits for the response a deferred response stands for; other responses are returned
+    /// as they are.
+    fn resolve(self) -> Self {
+        let mut response = self;
+        while let Some(deferred) = response.deferred.take() {
+            let (receiver, timeout) = *deferred;
+            response = match receiver.recv_timeout(timeout) {
+                Ok(response) => response,
+                Err(RecvTimeoutError::Timeout) => {
+                    log_error!("Deferred response not ready after {:?}", timeout);
+                    HttpResponse::status(503)
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    log_error!("Deferred response dropped without being sent");
+                    HttpResponse::status(500)
+                }
+            };
+        }
+        response
+    }
+
+    /// Sends trailer fields after a streamed body, e.g. a checksum or a status only known once
+    /// the whole body has been produced.
+    ///
+    /// The field names are announced up front in a `Trailer` header. Once the body has been
+    /// read to its end, `trailers` computes the fields, which follow the last chunk. Trailers
+    /// need chunked transfer encoding, so they are only sent with bodies from
+    /// `HttpResponse::stream` or `HttpResponse::chunks` without a `Content-Length`, on
+    /// connections that stay open, by `Server::listener` and `Server::bind`; otherwise they
+    /// are dropped. Fields with invalid names or values are dropped like headers.
+    ///
+    /// # Parameters
+    /// - 'names' : The names of the trailer fields, e.g. `grpc-status`.
+    /// - 'trailers' : Computes the names and values of the trailer fields.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use guest_server::{HttpResponse, Server};
+    /// use std::io::{Read, Write};
+    /// use std::net::TcpStream;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/rows", |_| {
+    ///     let count = Arc::new(AtomicUsize::new(0));
+    ///     let counted = Arc::clone(&count);
+    ///     let rows = (1..=3).map(move |i| {
+    ///         counted.fetch_add(1, Ordering::SeqCst);
+    ///         format!("row {}\n", i).into_bytes()
+    ///     });
+    ///     HttpResponse::chunks(200, rows).with_trailers(&["X-Rows"], move || {
+    ///         vec![("X-Rows".to_string(), count.load(Ordering::SeqCst).to_string())]
+    ///     })
+    /// });
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// // The first request keeps the connection open, so its body is chunked
+    /// let mut stream = TcpStream::connect(handle.local_addrs()[0]).unwrap();
+    /// let requests = "GET /rows HTTP/1.1\r\nHost: test\r\n\r\n\
+    ///                 GET /rows HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n";
+    /// stream.write_all(requests.as_bytes()).unwrap();
+    /// let mut responses = String::new();
+    /// stream.read_to_string(&mut responses).unwrap();
+    /// assert!(responses.contains("\r\nTrailer: X-Rows\r\n"));
+    /// assert!(responses.contains("6\r\nrow 3\n\r\n0\r\nX-Rows: 3\r\n\r\nHTTP/1.1 200 OK\r\n"));
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// ```
+    pub fn with_trailers<F>(mut self, names: &[&str], trailers: F) -> Self
+    where
+        F: FnOnce() -> Vec<(String, String)> + Send + 'static,
+    {
+        self.trailers = Some(Box::new(trailers));
+        self.insert_header("Trailer", &names.join(", "))
+    }
+
+    /// Reports whether the body is streamed in chunks: streamed bodies of unknown length on
+    /// connections that stay open need chunked framing so the client can tell where they end.
+    fn is_chunked(&self) -> bool {
+        self.stream.is_some() && !self.close && !self.has_header("Content-Length")
+    }
+
+    /// Closes the connection once this response is sent, even if the client would keep it
+    /// alive, by sending `Connection: close`.
+    ///
+    /// Useful after errors that leave the connection in doubt or before a very large response,
+    /// so the next request starts on a fresh connection.
+    pub fn close_connection(mut self) -> Self {
+        self.close = true;
+        self
+    }
+
+    /// Adds a cookie to set on the client.
+    ///
+    /// # Parameters
+    /// - 'cookie' : The cookie, sent as its own `Set-Cookie` header.
+    pub fn set_cookie(mut self, cookie: Cookie) -> Self {
+        self.cookies.push(cookie);
+        self
+    }
+
     /// Adds or updates multiple header fields in batch.
     ///
     /// # Parameters
@@ -359,18 +5082,98 @@ impl HttpResponse {
         self
     }
 
+    /// Reports whether a header is set, comparing names case-insensitively.
+    fn has_header(&self, key: &str) -> bool {
+        self.headers.keys().any(|k| k.eq_ignore_ascii_case(key))
+    }
+
+    /// Sets the reason phrase sent after the status code, e.g. for a nonstandard status code,
+    /// in place of the standard one.
+    ///
+    /// Clients go by the status code alone; the phrase is for people reading the exchange.
+    /// A phrase holding control characters such as CR or LF is not sent, and the standard one
+    /// is used instead.
+    ///
+    /// # Parameters
+    /// - 'reason' : The reason phrase, e.g. `Partially Stored`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use guest_server::HttpResponse;
+    ///
+    /// let response = HttpResponse::new(299, None).with_reason("Partially Stored");
+    /// assert_eq!(response.reason(), "Partially Stored");
+    /// assert_eq!(HttpResponse::new(299, None).reason(), "");
+    /// assert_eq!(HttpResponse::new(418, None).reason(), "I'm a teapot");
+    /// ```
+    pub fn with_reason(mut self, reason: &str) -> Self {
+        self.reason = Some(reason.to_string());
+        self
+    }
+
+    /// Returns the reason phrase to send: the one set with `with_reason`, else the standard
+    /// one from `get_status_message`.
+    pub fn reason(&self) -> &str {
+        self.reason
+            .as_deref()
+            .unwrap_or_else(|| self.get_status_message())
+    }
+
     /// Retrieves the description message for the status code.
     ///
     /// # Returns
-    /// A string representing the status message for the given status code.
+    /// A string representing the standard status message for the given status code, or an
+    /// empty string for codes without one, such as vendor-specific codes; an empty reason
+    /// phrase is valid in a status line, where a made-up one would mislead. Use `with_reason`
+    /// to send a phrase for such codes.
     pub fn get_status_message(&self) -> &'static str {
         match self.status_code {
+            100 => "Continue",
+            101 => "Switching Protocols",
+            103 => "Early Hints",
             200 => "OK",
             201 => "Created",
+            202 => "Accepted",
+            204 => "No Content",
+            206 => "Partial Content",
+            301 => "Moved Permanently",
+            302 => "Found",
+            303 => "See Other",
+            304 => "Not Modified",
+            307 => "Temporary Redirect",
+            308 => "Permanent Redirect",
             400 => "Bad Request",
+            401 => "Unauthorized",
+            402 => "Payment Required",
+            403 => "Forbidden",
             404 => "Not Found",
+            405 => "Method Not Allowed",
+            406 => "Not Acceptable",
+            408 => "Request Timeout",
+            409 => "Conflict",
+            410 => "Gone",
+            411 => "Length Required",
+            412 => "Precondition Failed",
+            413 => "Payload Too Large",
+            414 => "URI Too Long",
+            415 => "Unsupported Media Type",
+            416 => "Range Not Satisfiable",
+            417 => "Expectation Failed",
+            418 => "I'm a teapot",
+            422 => "Unprocessable Content",
+            426 => "Upgrade Required",
+            428 => "Precondition Required",
+            429 => "Too Many Requests",
+            431 => "Request Header Fields Too Large",
+            451 => "Unavailable For Legal Reasons",
             500 => "Internal Server Error",
-            _ => "Unknown Status",
+            501 => "Not Implemented",
+            502 => "Bad Gateway",
+            503 => "Service Unavailable",
+            504 => "Gateway Timeout",
+            505 => "HTTP Version Not Supported",
+            _ => "",
         }
     }
 }
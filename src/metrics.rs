@@ -0,0 +1,151 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        PoisonError, RwLock,
+    },
+    time::Duration,
+};
+
+/// Upper bounds, in seconds, of the request latency histogram buckets.
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Request counters and latency histogram for a `Server`, rendered in the Prometheus text
+/// exposition format.
+///
+/// Obtained from `Server::metrics`. Counters are atomics, so recording a request never blocks
+/// on other requests except the first time a method/path/status combination is seen.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use guest_server::{HttpResponse,Server};
+///
+/// let mut server = Server::new();
+/// let metrics = server.metrics();
+/// server.get("/metrics", move |_| {
+///     HttpResponse::new(200, Some(metrics.render()))
+///         .insert_header("Content-Type", "text/plain; version=0.0.4")
+/// });
+/// server.listener(8080);
+/// ```
+pub struct Metrics {
+    requests: RwLock<HashMap<(String, String, u16), AtomicU64>>, // Keyed by method, path, status.
+    buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    latency_sum_us: AtomicU64,
+    latency_count: AtomicU64,
+}
+
+impl Metrics {
+    pub(crate) fn new() -> Self {
+        Self {
+            requests: RwLock::new(HashMap::new()),
+            buckets: Default::default(),
+            latency_sum_us: AtomicU64::new(0),
+            latency_count: AtomicU64::new(0),
+        }
+    }
+
+    /// Records a completed request.
+    ///
+    /// # Parameters
+    /// - 'method' : The request method, empty if the request could not be parsed.
+    /// - 'path' : The pattern of the matched route or the prefix of the proxy; empty for
+    ///   any other request, including one that could not be parsed.
+    /// - 'status_code' : The status code of the response.
+    /// - 'latency' : The time taken to produce the response.
+    pub(crate) fn record(&self, method: &str, path: &str, status_code: u16, latency: Duration) {
+        let key = (method.to_string(), path.to_string(), status_code);
+        let counted = match self
+            .requests
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&key)
+        {
+            Some(counter) => {
+                counter.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        };
+        if !counted {
+            self.requests
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)
+                .entry(key)
+                .or_default()
+                .fetch_add(1, Ordering::Relaxed);
+        }
+
+        let seconds = latency.as_secs_f64();
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|le| seconds <= *le) {
+            self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        self.latency_sum_us
+            .fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+        self.latency_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    ///
+    /// # Returns
+    /// The `http_requests_total` counter by method, path and status, and the
+    /// `http_request_duration_seconds` histogram.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        out.push_str(
+            "# HELP http_requests_total Total HTTP requests by method, path and status.\n",
+        );
+        out.push_str("# TYPE http_requests_total counter\n");
+        let requests = self.requests.read().unwrap_or_else(PoisonError::into_inner);
+        let mut keys: Vec<_> = requests.keys().collect();
+        keys.sort();
+        for key in keys {
+            let (method, path, status_code) = key;
+            let _ = writeln!(
+                out,
+                "http_requests_total{{method=\"{}\",path=\"{}\",status=\"{}\"}} {}",
+                escape_label(method),
+                escape_label(path),
+                status_code,
+                requests[key].load(Ordering::Relaxed)
+            );
+        }
+
+        out.push_str("# HELP http_request_duration_seconds HTTP request latency.\n");
+        out.push_str("# TYPE http_request_duration_seconds histogram\n");
+        let mut cumulative = 0;
+        for (le, bucket) in LATENCY_BUCKETS.iter().zip(&self.buckets) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_bucket{{le=\"{}\"}} {}",
+                le, cumulative
+            );
+        }
+        let count = self.latency_count.load(Ordering::Relaxed);
+        let _ = writeln!(
+            out,
+            "http_request_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+            count
+        );
+        let _ = writeln!(
+            out,
+            "http_request_duration_seconds_sum {}",
+            self.latency_sum_us.load(Ordering::Relaxed) as f64 / 1_000_000.0
+        );
+        let _ = writeln!(out, "http_request_duration_seconds_count {}", count);
+        out
+    }
+}
+
+/// Escapes a label value as required by the exposition format.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
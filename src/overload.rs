@@ -0,0 +1,48 @@
+/// The state of a listener when it accepts a connection, as seen by an `OverloadPolicy`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Load {
+    /// Connections currently open, across all listeners, not counting the new one.
+    pub active_connections: usize,
+    /// The limit set with `Server::max_connections`, if any.
+    pub max_connections: Option<usize>,
+    /// How many times in a row accepting a connection failed before this one, e.g. because
+    /// the process ran out of file descriptors. Reset once a connection is accepted.
+    pub accept_errors: usize,
+}
+
+/// Decides whether the server is too busy to serve a newly accepted connection.
+///
+/// `Server::listener` consults the policy set with `Server::overload_policy` for every
+/// connection it accepts. An overloaded server answers at once with 503 Service Unavailable
+/// and `Retry-After`, so clients back off instead of stalling in the listen backlog until
+/// their connection is reset.
+///
+/// Closures taking a `&Load` implement this trait.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use guest_server::{AtCapacity,Load,Server};
+///
+/// let mut server = Server::new();
+/// server.max_connections(1000, AtCapacity::Wait);
+/// // Turn clients away early once 90% of the connections are in use, or when accept fails
+/// server.overload_policy(|load: &Load| {
+///     let near_cap = load.max_connections.is_some_and(|max| load.active_connections * 10 >= max * 9);
+///     near_cap || load.accept_errors > 0
+/// });
+/// server.listener(8080);
+/// ```
+pub trait OverloadPolicy: Send + Sync + 'static {
+    /// Reports whether a newly accepted connection should be turned away with 503.
+    fn is_overloaded(&self, load: &Load) -> bool;
+}
+
+impl<F> OverloadPolicy for F
+where
+    F: Fn(&Load) -> bool + Send + Sync + 'static,
+{
+    fn is_overloaded(&self, load: &Load) -> bool {
+        self(load)
+    }
+}
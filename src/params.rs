@@ -0,0 +1,113 @@
+use std::{collections::HashMap, ops::Index, str::FromStr};
+
+/// The path parameters a route captured from a request, e.g. `id` => `42` for the route
+/// `/users/:id` and the path `/users/42`, as found in `Request::params`.
+///
+/// Values are percent-decoded strings; `get_as` parses them into other types.
+///
+/// # Example
+///
+/// ```rust
+/// use guest_server::Params;
+/// use std::collections::HashMap;
+///
+/// let params = Params::from(HashMap::from([("id".to_string(), "42".to_string())]));
+/// assert_eq!(params.get("id"), Some("42"));
+/// assert_eq!(params["id"], "42");
+/// assert_eq!(params.get_as::<u64>("id"), Ok(Some(42)));
+/// assert!(params.get_as::<bool>("id").is_err());
+/// assert_eq!(params.get_as::<u64>("name"), Ok(None));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Params {
+    params: HashMap<String, String>,
+}
+
+impl Params {
+    /// Retrieves the value of a parameter.
+    ///
+    /// # Parameters
+    /// - 'name' : The parameter name, as in the route path without its `:` or `*`.
+    ///
+    /// # Returns
+    /// The decoded value, or `None` if the route has no such parameter.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(String::as_str)
+    }
+
+    /// Retrieves the value of a parameter parsed into `T`, e.g. a numeric ID.
+    ///
+    /// The two failures mean different things. A parse error is the client's: the path
+    /// matched the route, but the segment is not a valid `T`, e.g. `/users/abc` for a numeric
+    /// `:id`, so the handler should answer 400 Bad Request. `Ok(None)` is the program's: the
+    /// route captures no parameter of that name, which no request can fix, so it usually
+    /// points at a typo in the route or the handler.
+    ///
+    /// # Parameters
+    /// - 'name' : The parameter name, as in the route path without its `:` or `*`.
+    ///
+    /// # Returns
+    /// `Ok(Some(value))` if the parameter parses, the parse error if it does not, or
+    /// `Ok(None)` if the route has no such parameter.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "testing")] {
+    /// use guest_server::{HttpResponse, Server, TestClient};
+    ///
+    /// let mut server = Server::new();
+    /// server.get("/users/:id", |request| {
+    ///     let id = match request.params.get_as::<u64>("id") {
+    ///         Ok(id) => id.expect("the route captures :id"),
+    ///         Err(_) => return HttpResponse::status(400),
+    ///     };
+    ///     HttpResponse::from_string(200, format!("user {}", id))
+    /// });
+    /// let handle = server.bind("127.0.0.1:0").unwrap();
+    ///
+    /// let client = TestClient::new(handle.local_addrs()[0]);
+    /// assert_eq!(client.get("/users/42").unwrap().text(), "user 42");
+    /// assert_eq!(client.get("/users/abc").unwrap().status_code, 400);
+    ///
+    /// handle.shutdown();
+    /// handle.join();
+    /// # }
+    /// ```
+    pub fn get_as<T: FromStr>(&self, name: &str) -> Result<Option<T>, T::Err> {
+        self.get(name).map(str::parse).transpose()
+    }
+
+    /// Returns all parameters as a map of names to values.
+    pub fn as_map(&self) -> &HashMap<String, String> {
+        &self.params
+    }
+
+    /// Returns the number of parameters.
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    /// Reports whether the route captured no parameters.
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+}
+
+impl From<HashMap<String, String>> for Params {
+    fn from(params: HashMap<String, String>) -> Self {
+        Self { params }
+    }
+}
+
+/// Returns the value of a parameter.
+///
+/// # Panics
+/// If the route has no parameter of that name.
+impl Index<&str> for Params {
+    type Output = String;
+
+    fn index(&self, name: &str) -> &String {
+        &self.params[name]
+    }
+}
@@ -0,0 +1,275 @@
+use std::{
+    io::{self, BufRead, BufReader, Error, ErrorKind, Write},
+    net::{IpAddr, Shutdown, TcpStream, ToSocketAddrs},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{Config, HttpMethod, HttpResponse, Request, RequestReader, Server};
+
+/// Headers that describe a single connection rather than the message, so they are never
+/// forwarded in either direction.
+const HOP_BY_HOP: [&str; 8] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+];
+
+/// Longest wait for a connection to an upstream or tunnel target to be established.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest wait for an upstream to accept more of the request or send more of its response.
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Decides which `host:port` authorities CONNECT requests may open a tunnel to, as set with
+/// `Server::tunnel`.
+pub(crate) type TunnelFilter = Arc<dyn Fn(&str) -> bool + Send + Sync + 'static>;
+
+/// A path prefix whose requests are forwarded to an upstream server, as configured with
+/// `Server::proxy`.
+#[derive(Clone)]
+pub(crate) struct Proxy {
+    pub(crate) prefix: String,
+    pub(crate) upstream: String, // Address of the upstream server, e.g. `127.0.0.1:9000`.
+}
+
+impl Proxy {
+    /// Reports whether a request path falls under the prefix, on a segment boundary.
+    pub(crate) fn matches(&self, path: &str) -> bool {
+        match path.strip_prefix(&self.prefix) {
+            Some(rest) => rest.is_empty() || rest.starts_with('/') || self.prefix.ends_with('/'),
+            None => false,
+        }
+    }
+
+    /// Forwards a request to the upstream and streams its response back to the client.
+    ///
+    /// The upstream is asked for an HTTP/1.0 response so that its body is delimited by the
+    /// connection closing and can be copied through as it arrives. If the upstream cannot be
+    /// reached or answers with something other than HTTP, the client gets 502 Bad Gateway.
+    ///
+    /// # Parameters
+    /// - 'request' : The parsed client request.
+    /// - 'config' : The server-wide settings, used for the 502 response and metrics.
+    /// - 'stream' : The client connection.
+    ///
+    /// # Returns
+    /// An error if writing to the client failed.
+    pub(crate) fn forward(
+        &self,
+        request: &Request,
+        config: &Config,
+        stream: &mut TcpStream,
+    ) -> Result<(), Error> {
+        let start = Instant::now();
+        let client = stream.peer_addr()?.ip();
+        let (status_code, head, mut upstream) = match self.exchange(request, &client.to_string()) {
+            Ok(exchange) => exchange,
+            Err(e) => {
+                log_error!("Upstream {} failed: {}", self.upstream, e);
+                let response =
+                    Server::render_error(HttpResponse::status(502), config).close_connection();
+                let is_head = request.method == HttpMethod::HEAD;
+                let res =
+                    Server::generate_http_response(&response, config, is_head, &request.request_id);
+                record(request, &self.prefix, config, client, 502, 0, start);
+                Server::report_error(request, &response, config);
+                return stream.write_all(&res);
+            }
+        };
+
+        stream.write_all(head.as_bytes())?;
+        let size = io::copy(&mut upstream, stream)?;
+        let size = size as usize;
+        record(
+            request,
+            &self.prefix,
+            config,
+            client,
+            status_code,
+            size,
+            start,
+        );
+        Ok(())
+    }
+
+    /// Sends the request upstream and reads the head of its response.
+    ///
+    /// # Returns
+    /// The upstream status code, the response head rewritten for the client, and the reader
+    /// positioned at the start of the response body.
+    fn exchange(
+        &self,
+        request: &Request,
+        client_ip: &str,
+    ) -> Result<(u16, String, BufReader<TcpStream>), Error> {
+        let mut upstream = connect(&self.upstream)?;
+        upstream.set_read_timeout(Some(UPSTREAM_TIMEOUT))?;
+        upstream.set_write_timeout(Some(UPSTREAM_TIMEOUT))?;
+
+        let mut head = format!("{} {}", request.method, request.path);
+        if !request.query.as_str().is_empty() {
+            head.push('?');
+            head.push_str(request.query.as_str());
+        }
+        head.push_str(" HTTP/1.0\r\n");
+        // Headers named by Connection are hop-by-hop too
+        let connection = request
+            .headers
+            .get("connection")
+            .map(|value| value.to_lowercase())
+            .unwrap_or_default();
+        for (name, value) in &request.headers {
+            let hop_by_hop = HOP_BY_HOP.contains(&name.as_str())
+                || connection.split(',').any(|token| token.trim() == name);
+            if !hop_by_hop && name != "content-length" && name != "x-forwarded-for" {
+                head.push_str(&format!("{}: {}\r\n", name, value));
+            }
+        }
+        let forwarded_for = match request.headers.get("x-forwarded-for") {
+            Some(previous) => format!("{}, {}", previous, client_ip),
+            None => client_ip.to_string(),
+        };
+        head.push_str(&format!("X-Forwarded-For: {}\r\n", forwarded_for));
+        if !request.headers.contains_key("x-request-id") {
+            head.push_str(&format!("X-Request-Id: {}\r\n", request.request_id));
+        }
+        head.push_str(&format!("Content-Length: {}\r\n\r\n", request.body.len()));
+        upstream.write_all(head.as_bytes())?;
+        upstream.write_all(&request.body)?;
+
+        let mut upstream = BufReader::new(upstream);
+        let mut status_line = String::new();
+        upstream.read_line(&mut status_line)?;
+        let invalid = || Error::new(ErrorKind::InvalidData, "Malformed upstream response");
+        let (version, rest) = status_line.split_once(' ').ok_or_else(invalid)?;
+        if !version.starts_with("HTTP/") {
+            return Err(invalid());
+        }
+        let status_code = rest
+            .split_whitespace()
+            .next()
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(invalid)?;
+
+        let mut head = format!("HTTP/1.1 {}", rest);
+        loop {
+            let mut line = String::new();
+            if upstream.read_line(&mut line)? == 0 {
+                return Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "Incomplete upstream header",
+                ));
+            }
+            if line.trim_end().is_empty() {
+                break;
+            }
+            let hop_by_hop = line
+                .split_once(':')
+                .is_some_and(|(name, _)| HOP_BY_HOP.contains(&name.trim().to_lowercase().as_str()));
+            if !hop_by_hop {
+                head.push_str(&line);
+            }
+        }
+        head.push_str("Connection: close\r\n\r\n");
+
+        Ok((status_code, head, upstream))
+    }
+}
+
+/// Opens a tunnel for a CONNECT request and relays bytes both ways until either side closes.
+///
+/// The client gets 200 once the target, named by the request's `host:port` target, is
+/// connected, or 502 Bad Gateway if it cannot be reached. Read timeouts are lifted for the
+/// tunnel, since it may carry a protocol that is idle for long periods.
+///
+/// # Parameters
+/// - 'request' : The CONNECT request; its path holds the target authority.
+/// - 'config' : The server-wide settings, used for the 502 response and metrics.
+/// - 'reader' : The buffered client connection, which may hold bytes sent after the request.
+/// - 'stream' : The client connection, for writing.
+///
+/// # Returns
+/// An error if relaying failed other than by either side closing.
+pub(crate) fn tunnel(
+    request: &Request,
+    config: &Config,
+    reader: BufReader<RequestReader>,
+    stream: &mut TcpStream,
+) -> Result<(), Error> {
+    let start = Instant::now();
+    let client = stream.peer_addr()?.ip();
+    let mut upstream = match connect(&request.path) {
+        Ok(upstream) => upstream,
+        Err(e) => {
+            log_error!("Tunnel to {} failed: {}", request.path, e);
+            let response =
+                Server::render_error(HttpResponse::status(502), config).close_connection();
+            let res = Server::generate_http_response(&response, config, false, &request.request_id);
+            record(request, "", config, client, 502, 0, start);
+            Server::report_error(request, &response, config);
+            return stream.write_all(&res);
+        }
+    };
+    stream.write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")?;
+    record(request, "", config, client, 200, 0, start);
+    stream.set_read_timeout(None)?;
+    upstream.write_all(reader.buffer())?;
+
+    // Client to upstream on a thread of its own, upstream to client on this one
+    let mut to_upstream = upstream.try_clone()?;
+    let mut from_client = reader.into_inner().into_inner();
+    let outbound = thread::spawn(move || {
+        let result = io::copy(&mut from_client, &mut to_upstream);
+        let _ = to_upstream.shutdown(Shutdown::Write);
+        result
+    });
+    let inbound = io::copy(&mut upstream, stream);
+    let _ = stream.shutdown(Shutdown::Write);
+    let outbound = outbound.join().unwrap_or(Ok(0));
+    inbound.and(outbound).map(|_| ())
+}
+
+/// Connects to a `host:port` authority, trying each address it resolves to in turn, each for
+/// at most `CONNECT_TIMEOUT`.
+///
+/// # Returns
+/// The connection, or the error of the last address tried.
+fn connect(authority: &str) -> Result<TcpStream, Error> {
+    let mut last_error = Error::new(ErrorKind::NotFound, "No address to connect to");
+    for addr in authority.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+/// Records a proxied or tunneled request like any other served request, counting it in the
+/// metrics under `route`: the proxy prefix, or empty for a tunnel.
+fn record(
+    request: &Request,
+    route: &str,
+    config: &Config,
+    client: IpAddr,
+    status_code: u16,
+    size: usize,
+    start: Instant,
+) {
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record("status", status_code);
+    config.counters.response(status_code);
+    if let Some(metrics) = &config.metrics {
+        metrics.record(request.method.as_str(), route, status_code, start.elapsed());
+    }
+    if let Some(access_log) = &config.access_log {
+        access_log.write(Some(client), Some(request), status_code, size);
+    }
+}
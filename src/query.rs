@@ -0,0 +1,154 @@
+use std::{collections::HashMap, str::FromStr};
+
+/// The parameters of a request's query string, e.g. `page=2&sort=name`.
+///
+/// Keys and values are percent-decoded and `+` is read as a space. When a key is repeated,
+/// the last value wins; use `multi` to collect every value of array parameters instead.
+///
+/// # Example
+///
+/// ```rust
+/// use guest_server::Query;
+///
+/// let query = Query::parse("page=2&sort=name&q=hello+world");
+/// assert_eq!(query.get("q"), Some("hello world"));
+/// assert_eq!(query.get_as::<i32>("page"), Ok(Some(2)));
+/// assert_eq!(query.get_as::<i32>("limit"), Ok(None));
+/// assert!(query.get_as::<i32>("sort").is_err());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Query {
+    raw: String,
+    params: HashMap<String, String>,
+}
+
+impl Query {
+    /// Parses a query string (without the leading `?`).
+    ///
+    /// # Parameters
+    /// - 'query' : The raw query string.
+    ///
+    /// # Returns
+    /// The parsed parameters. Pairs without `=` get an empty value; malformed percent-escapes
+    /// are kept as written.
+    pub fn parse(query: &str) -> Self {
+        Self {
+            raw: query.to_string(),
+            params: pairs(query).collect(),
+        }
+    }
+
+    /// Parses a query string or `application/x-www-form-urlencoded` body, collecting every
+    /// value of repeated keys.
+    ///
+    /// Both array conventions are understood: plain repetition (`tags=a&tags=b`) and the
+    /// bracket syntax (`tags[]=a&tags[]=b`), whose `[]` suffix is dropped from the key. Mixing
+    /// them collects all values under the bare key, in order of appearance. Indexed keys such
+    /// as `tags[0]` are kept as written.
+    ///
+    /// # Parameters
+    /// - 'query' : The raw query string or form body.
+    ///
+    /// # Returns
+    /// A map of each key to all of its values.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use guest_server::Query;
+    ///
+    /// let params = Query::parse_multi("tags[]=a&tags[]=b&tags=c&page=1");
+    /// assert_eq!(params["tags"], ["a", "b", "c"]);
+    /// assert_eq!(params["page"], ["1"]);
+    /// ```
+    pub fn parse_multi(query: &str) -> HashMap<String, Vec<String>> {
+        let mut params: HashMap<String, Vec<String>> = HashMap::new();
+        for (mut key, value) in pairs(query) {
+            if key.ends_with("[]") {
+                key.truncate(key.len() - 2);
+            }
+            params.entry(key).or_default().push(value);
+        }
+        params
+    }
+
+    /// Returns the parameters with every value of repeated keys. See `Query::parse_multi`.
+    pub fn multi(&self) -> HashMap<String, Vec<String>> {
+        Query::parse_multi(&self.raw)
+    }
+
+    /// Retrieves the value of a parameter.
+    ///
+    /// # Parameters
+    /// - 'key' : The parameter name.
+    ///
+    /// # Returns
+    /// The decoded value, or `None` if the parameter is absent.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.params.get(key).map(String::as_str)
+    }
+
+    /// Retrieves the value of a parameter parsed into `T`.
+    ///
+    /// # Parameters
+    /// - 'key' : The parameter name.
+    ///
+    /// # Returns
+    /// `Ok(None)` if the parameter is absent, `Ok(Some(value))` if it parses, and the parse
+    /// error if it is present but not a valid `T`, so handlers can answer 400.
+    pub fn get_as<T: FromStr>(&self, key: &str) -> Result<Option<T>, T::Err> {
+        self.get(key).map(str::parse).transpose()
+    }
+
+    /// Returns the query string as it was received, still encoded.
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// Returns all parameters as a map of names to values.
+    pub fn as_map(&self) -> &HashMap<String, String> {
+        &self.params
+    }
+}
+
+/// Splits a query string into its decoded key/value pairs, in order of appearance.
+fn pairs(query: &str) -> impl Iterator<Item = (String, String)> + '_ {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode_component(key), decode_component(value))
+        })
+}
+
+/// Decodes one `application/x-www-form-urlencoded` key or value, leaving it untouched if it
+/// is not validly encoded.
+fn decode_component(input: &str) -> String {
+    percent_decode(&input.replace('+', " ")).unwrap_or_else(|| input.to_string())
+}
+
+/// Decodes `%XX` escapes in a string.
+///
+/// # Returns
+/// The decoded string, or `None` if an escape is malformed or the result is not UTF-8.
+pub(crate) fn percent_decode(input: &str) -> Option<String> {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes.get(i + 1..i + 3)?;
+            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+            let hex = std::str::from_utf8(hex).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
@@ -0,0 +1,132 @@
+use std::{
+    io::{self, Cursor, Read},
+    panic::{self, AssertUnwindSafe},
+};
+
+use crate::HttpResponse;
+
+/// A value a handler can return in place of a fully built `HttpResponse`.
+///
+/// Route handlers may return any type implementing this trait; it is converted once the
+/// handler returns. Implemented for:
+/// - `HttpResponse`, sent as is;
+/// - `&'static str` and `String`, sent as a 200 `text/plain` body; a string constant is sent
+///   from where it is, without being copied;
+/// - `(u16, String)`, sent as a `text/plain` body with the given status code;
+/// - `u16`, sent as a response with that status code and no body;
+/// - `Vec<u8>`, sent as a 200 `application/octet-stream` body, which may be binary.
+/// - `Result<T, E>` where both implement the trait, sending whichever the handler returned,
+///   e.g. `Result<String, HttpResponse>` with `HttpResponse::problem` errors.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use guest_server::Server;
+///
+/// let mut server = Server::new();
+/// server.get("/", |_| "Hello, World!");
+/// server.get("/teapot", |_| (418, "I'm a teapot".to_string()));
+/// server.listener(8080);
+/// ```
+pub trait IntoResponse {
+    /// Converts the value into the response to send.
+    fn into_response(self) -> HttpResponse;
+}
+
+impl IntoResponse for HttpResponse {
+    fn into_response(self) -> HttpResponse {
+        self
+    }
+}
+
+impl IntoResponse for &'static str {
+    fn into_response(self) -> HttpResponse {
+        HttpResponse::from_static(200, self)
+    }
+}
+
+impl IntoResponse for String {
+    fn into_response(self) -> HttpResponse {
+        HttpResponse::new(200, Some(self))
+    }
+}
+
+impl IntoResponse for u16 {
+    fn into_response(self) -> HttpResponse {
+        HttpResponse::status(self)
+    }
+}
+
+impl IntoResponse for (u16, String) {
+    fn into_response(self) -> HttpResponse {
+        HttpResponse::new(self.0, Some(self.1))
+    }
+}
+
+impl IntoResponse for Vec<u8> {
+    fn into_response(self) -> HttpResponse {
+        // The body is not text, so it is sent as a stream of known length
+        let length = self.len().to_string();
+        HttpResponse::stream(200, Cursor::new(self)).insert_header("Content-Length", &length)
+    }
+}
+
+impl<T: IntoResponse, E: IntoResponse> IntoResponse for Result<T, E> {
+    fn into_response(self) -> HttpResponse {
+        match self {
+            Ok(value) => value.into_response(),
+            Err(error) => error.into_response(),
+        }
+    }
+}
+
+/// Reads the chunks an iterator yields as one body, for `HttpResponse::chunks`.
+pub(crate) struct ChunkReader<I> {
+    chunks: I,
+    current: Cursor<Vec<u8>>,
+}
+
+impl<I: Iterator<Item = Vec<u8>>> ChunkReader<I> {
+    pub(crate) fn new(chunks: I) -> Self {
+        Self {
+            chunks,
+            current: Cursor::new(Vec::new()),
+        }
+    }
+}
+
+impl<I: Iterator<Item = Vec<u8>>> Read for ChunkReader<I> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let read = self.current.read(buffer)?;
+            if read > 0 || buffer.is_empty() {
+                return Ok(read);
+            }
+            // A panicking iterator fails the read, which aborts the response mid-stream
+            match panic::catch_unwind(AssertUnwindSafe(|| self.chunks.next())) {
+                Ok(Some(chunk)) => self.current = Cursor::new(chunk),
+                Ok(None) => return Ok(0),
+                Err(_) => return Err(io::Error::other("Body iterator panicked")),
+            }
+        }
+    }
+}
+
+/// Renders a string as a JSON string literal, quotes included.
+pub(crate) fn json_string(value: &str) -> String {
+    let mut literal = String::with_capacity(value.len() + 2);
+    literal.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            c if c.is_control() => literal.push_str(&format!("\\u{:04x}", c as u32)),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
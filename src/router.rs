@@ -0,0 +1,286 @@
+use std::{collections::HashMap, fmt, time::Duration};
+
+use crate::{Handler, HttpMethod};
+
+/// What a `Router` stores for each route: the handler and its per-route settings.
+#[derive(Clone)]
+pub struct Route {
+    pub handler: Handler,
+    /// How long the handler may run, overriding `Server::handler_timeout` for this route.
+    pub timeout: Option<Duration>,
+    /// The path the route was registered with, e.g. `/users/:id`, exposed to the handler as
+    /// `Request::route`.
+    pub pattern: Option<String>,
+}
+
+impl Route {
+    /// Creates a route for a handler, with the server-wide settings and no pattern.
+    pub fn new(handler: Handler) -> Self {
+        Self {
+            handler,
+            timeout: None,
+            pattern: None,
+        }
+    }
+}
+
+/// The route a `Router` found for a request.
+pub struct Match {
+    pub route: Route,
+    /// The path parameters captured by the route, keyed by name; exposed to the handler as
+    /// `Request::params`.
+    pub params: HashMap<String, String>,
+}
+
+/// The routes registered with a `Server`, as returned by `Server::routes`.
+///
+/// Displays as a table with one route per line, sorted by path and, within a path, by
+/// method, e.g. for a `--routes` command line flag.
+///
+/// # Example
+///
+/// ```rust
+/// use guest_server::Server;
+///
+/// let mut server = Server::new();
+/// server.post("/users", |_| "created");
+/// server.get("/users/:id", |_| "user");
+/// server.get("/users", |_| "users");
+///
+/// assert_eq!(
+///     server.routes().to_string(),
+///     "GET    /users\nPOST   /users\nGET    /users/:id\n"
+/// );
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct RouteTable {
+    entries: Vec<(HttpMethod, String)>,
+}
+
+impl RouteTable {
+    /// Records a route, once per method and path, as the router replaces duplicates.
+    pub(crate) fn add(&mut self, method: HttpMethod, path: &str) {
+        if !self.entries.iter().any(|(m, p)| *m == method && p == path) {
+            self.entries.push((method, path.to_string()));
+        }
+    }
+
+    /// Returns the method and path of each route, in registration order.
+    pub fn iter(&self) -> impl Iterator<Item = (&HttpMethod, &str)> {
+        self.entries
+            .iter()
+            .map(|(method, path)| (method, path.as_str()))
+    }
+
+    /// Returns the number of routes.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Reports whether no routes are registered.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl fmt::Display for RouteTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const ORDER: [HttpMethod; 3] = [HttpMethod::GET, HttpMethod::POST, HttpMethod::PATCH];
+        let mut entries: Vec<&(HttpMethod, String)> = self.entries.iter().collect();
+        entries.sort_by_key(|(method, path)| (path, ORDER.iter().position(|m| m == method)));
+        for (method, path) in entries {
+            writeln!(f, "{:<6} {}", method, path)?;
+        }
+        Ok(())
+    }
+}
+
+/// The strategy a `Server` uses to map a request's method and path to a handler.
+///
+/// `TrieRouter` is the default. Implement this trait to plug in a different matching scheme,
+/// e.g. a regex or host-based router, and pass it to `Server::with_router`.
+///
+/// The server keeps using a router after `add` or `find` panicked, so implementations should
+/// leave their state consistent at every point where they can panic.
+pub trait Router: Send + Sync + 'static {
+    /// Registers a route for a method and path, replacing any route already registered for
+    /// them.
+    fn add(&mut self, method: HttpMethod, path: &str, route: Route);
+
+    /// Looks up the handler for a request.
+    ///
+    /// # Parameters
+    /// - 'method' : The request method. HEAD requests are looked up as GET.
+    /// - 'path' : The request path, without the query string.
+    ///
+    /// # Returns
+    /// The matching handler, or `None` to answer 405 if the path has routes for other
+    /// methods, else 404 (or run the server's fallback).
+    fn find(&self, method: &HttpMethod, path: &str) -> Option<Match>;
+
+    /// Lists the methods with a route matching a path, for answering 405 Method Not Allowed.
+    ///
+    /// The default looks the path up with `find` for each method routes can be registered
+    /// for; override it if the router can answer more cheaply.
+    ///
+    /// # Returns
+    /// The methods in registration-independent order, empty if no route matches the path.
+    fn allowed_methods(&self, path: &str) -> Vec<HttpMethod> {
+        [HttpMethod::GET, HttpMethod::POST, HttpMethod::PATCH]
+            .into_iter()
+            .filter(|method| self.find(method, path).is_some())
+            .collect()
+    }
+}
+
+/// A `Router` backed by a hash map from method and exact path to handler.
+///
+/// Paths are compared literally, without parameters.
+#[derive(Default)]
+pub struct HashRouter {
+    routes: HashMap<(HttpMethod, String), Route>,
+}
+
+impl HashRouter {
+    /// Creates a router without routes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Router for HashRouter {
+    fn add(&mut self, method: HttpMethod, path: &str, route: Route) {
+        self.routes.insert((method, path.to_string()), route);
+    }
+
+    fn find(&self, method: &HttpMethod, path: &str) -> Option<Match> {
+        let route = self.routes.get(&(method.clone(), path.to_string()))?;
+        Some(Match {
+            route: route.clone(),
+            params: HashMap::new(),
+        })
+    }
+}
+
+/// A `Router` that matches paths segment by segment in a trie, supporting parameters.
+///
+/// Route paths are split at `/` into segments, each of which is one of:
+/// - a static segment such as `users`, matched exactly;
+/// - a parameter such as `:id`, matching any one non-empty segment, captured under `id`;
+/// - a wildcard such as `*path`, allowed only as the last segment, matching the rest of the
+///   path, captured under `path` with its slashes. `/files/*path` matches `/files/` with an
+///   empty `path`, but not `/files`.
+///
+/// When several routes match, static segments take precedence over parameters and
+/// parameters over wildcards, segment by segment from the left, so `/users/me` wins over
+/// `/users/:id` regardless of registration order. Lookup visits each path segment once per
+/// candidate branch, so its cost depends on the length of the path, not the number of routes.
+/// This is the router `Server::new` uses.
+///
+/// # Example
+///
+/// ```rust
+/// use guest_server::{HttpMethod, HttpResponse, Route, Router, TrieRouter};
+/// use std::sync::Arc;
+///
+/// let mut router = TrieRouter::new();
+/// let ok = Route::new(Arc::new(|_| HttpResponse::new(200, None)));
+/// router.add(HttpMethod::GET, "/users/:id", ok.clone());
+/// router.add(HttpMethod::GET, "/files/*path", ok);
+///
+/// let found = router.find(&HttpMethod::GET, "/users/42").unwrap();
+/// assert_eq!(found.params["id"], "42");
+/// let found = router.find(&HttpMethod::GET, "/files/css/site.css").unwrap();
+/// assert_eq!(found.params["path"], "css/site.css");
+/// assert!(router.find(&HttpMethod::GET, "/users").is_none());
+/// ```
+#[derive(Default)]
+pub struct TrieRouter {
+    roots: HashMap<HttpMethod, Node>,
+}
+
+/// A route with the names of the parameters captured on the way to it, in path order.
+type Leaf = (Route, Vec<String>);
+
+/// One segment position in the trie.
+#[derive(Default)]
+struct Node {
+    children: HashMap<String, Node>, // Static segments.
+    param: Option<Box<Node>>,        // Any single segment.
+    handler: Option<Leaf>,           // Routes ending at this node.
+    wildcard: Option<Leaf>,          // Routes capturing the rest of the path from this node.
+}
+
+impl TrieRouter {
+    /// Creates a router without routes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Router for TrieRouter {
+    fn add(&mut self, method: HttpMethod, path: &str, route: Route) {
+        let mut node = self.roots.entry(method).or_default();
+        let mut names = Vec::new();
+        let segments: Vec<&str> = segments(path).collect();
+        for (i, segment) in segments.iter().enumerate() {
+            if let Some(name) = segment
+                .strip_prefix('*')
+                .filter(|_| i == segments.len() - 1)
+            {
+                names.push(name.to_string());
+                node.wildcard = Some((route, names));
+                return;
+            }
+            node = match segment.strip_prefix(':') {
+                Some(name) => {
+                    names.push(name.to_string());
+                    node.param.get_or_insert_with(Default::default)
+                }
+                None => node.children.entry(segment.to_string()).or_default(),
+            };
+        }
+        node.handler = Some((route, names));
+    }
+
+    fn find(&self, method: &HttpMethod, path: &str) -> Option<Match> {
+        let segments: Vec<&str> = segments(path).collect();
+        let mut values = Vec::new();
+        let (route, names) = self.roots.get(method)?.find(&segments, &mut values)?;
+        Some(Match {
+            route: route.clone(),
+            params: names.iter().cloned().zip(values).collect(),
+        })
+    }
+}
+
+impl Node {
+    /// Finds the route matching the remaining segments, collecting captured values.
+    fn find(&self, segments: &[&str], values: &mut Vec<String>) -> Option<&Leaf> {
+        let Some((first, rest)) = segments.split_first() else {
+            return self.handler.as_ref();
+        };
+        if let Some(found) = self
+            .children
+            .get(*first)
+            .and_then(|child| child.find(rest, values))
+        {
+            return Some(found);
+        }
+        if let Some(param) = self.param.as_ref().filter(|_| !first.is_empty()) {
+            values.push(first.to_string());
+            if let Some(found) = param.find(rest, values) {
+                return Some(found);
+            }
+            values.pop();
+        }
+        let wildcard = self.wildcard.as_ref()?;
+        values.push(segments.join("/"));
+        Some(wildcard)
+    }
+}
+
+/// Splits a path into its segments, e.g. `/users/42` into `users` and `42`.
+fn segments(path: &str) -> std::str::Split<'_, char> {
+    path.strip_prefix('/').unwrap_or(path).split('/')
+}
@@ -0,0 +1,193 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "signed-cookies")]
+use crate::{Cookie, HttpResponse, SameSite, Server};
+
+/// The name of the signed cookie holding the session ID.
+#[cfg(feature = "signed-cookies")]
+pub(crate) const SESSION_COOKIE: &str = "session_id";
+
+/// Storage backend for session data, keyed by session ID.
+///
+/// `MemoryStore` is the default; implement this trait to keep sessions elsewhere (e.g. Redis)
+/// so they survive restarts or are shared between server instances.
+pub trait SessionStore: Send + Sync {
+    /// Loads the data of a session that has not expired.
+    fn load(&self, id: &str) -> Option<HashMap<String, String>>;
+
+    /// Stores the data of a session, replacing previous data and expiring it after `ttl`.
+    fn save(&self, id: &str, data: HashMap<String, String>, ttl: Duration);
+
+    /// Deletes a session.
+    fn remove(&self, id: &str);
+}
+
+/// Session data with its expiry time, keyed by session ID.
+type Entries = HashMap<String, (HashMap<String, String>, Instant)>;
+
+/// An in-memory `SessionStore`. Sessions are lost when the process exits.
+///
+/// Expired sessions are never returned and are evicted whenever a session is saved.
+#[derive(Default)]
+pub struct MemoryStore {
+    sessions: Mutex<Entries>,
+}
+
+impl MemoryStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SessionStore for MemoryStore {
+    fn load(&self, id: &str) -> Option<HashMap<String, String>> {
+        let sessions = self.sessions.lock().unwrap_or_else(PoisonError::into_inner);
+        let (data, expires) = sessions.get(id)?;
+        (*expires > Instant::now()).then(|| data.clone())
+    }
+
+    fn save(&self, id: &str, data: HashMap<String, String>, ttl: Duration) {
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().unwrap_or_else(PoisonError::into_inner);
+        sessions.retain(|_, (_, expires)| *expires > now);
+        sessions.insert(id.to_string(), (data, now + ttl));
+    }
+
+    fn remove(&self, id: &str) {
+        self.sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(id);
+    }
+}
+
+/// The session of the client making a request, available to handlers as `request.session`.
+///
+/// Values written by a handler are saved to the server's `SessionStore` after the response is
+/// generated and are visible to later requests carrying the same session cookie. Without
+/// sessions enabled on the server the session starts empty and is never saved.
+#[derive(Clone, Debug, Default)]
+pub struct Session {
+    id: Option<String>,
+    data: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl Session {
+    /// Creates a session restored from a store, or a new empty one when `id` is `None`.
+    #[cfg(feature = "signed-cookies")]
+    pub(crate) fn new(id: Option<String>, data: HashMap<String, String>) -> Self {
+        Self {
+            id,
+            data: Arc::new(Mutex::new(data)),
+        }
+    }
+
+    /// Returns the session ID, or `None` for a session that has not been saved yet.
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Retrieves a value from the session.
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.data
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(key)
+            .cloned()
+    }
+
+    /// Adds or updates a value in the session.
+    pub fn insert(&self, key: &str, value: &str) {
+        self.data
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(key.to_string(), value.to_string());
+    }
+
+    /// Removes a value from the session, returning it if it was present.
+    pub fn remove(&self, key: &str) -> Option<String> {
+        self.data
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(key)
+    }
+
+    /// Removes every value. An empty session is deleted from the store and its cookie expired.
+    pub fn clear(&self) {
+        self.data
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    /// Returns a copy of all values in the session.
+    #[cfg(feature = "signed-cookies")]
+    pub(crate) fn data(&self) -> HashMap<String, String> {
+        self.data
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+/// The store and lifetime used for sessions, as configured with `Server::sessions`.
+#[cfg(feature = "signed-cookies")]
+#[derive(Clone)]
+pub(crate) struct Sessions {
+    pub(crate) store: Arc<dyn SessionStore>,
+    pub(crate) ttl: Duration,
+}
+
+#[cfg(feature = "signed-cookies")]
+impl Sessions {
+    /// Restores the session named by the request's session cookie.
+    ///
+    /// # Returns
+    /// The stored session, or a new empty one if the cookie is missing, tampered with or
+    /// refers to an expired session.
+    pub(crate) fn restore(&self, signed_cookies: &HashMap<String, String>) -> Session {
+        signed_cookies
+            .get(SESSION_COOKIE)
+            .and_then(|id| {
+                let data = self.store.load(id)?;
+                Some(Session::new(Some(id.clone()), data))
+            })
+            .unwrap_or_default()
+    }
+
+    /// Saves the session after the handler ran and sets the session cookie on the response.
+    ///
+    /// Non-empty sessions are saved (new ones under a freshly generated ID) and the cookie is
+    /// re-sent so its expiry follows the store's. A session emptied by the handler is removed
+    /// and its cookie expired.
+    pub(crate) fn commit(&self, session: &Session, response: HttpResponse) -> HttpResponse {
+        let data = session.data();
+        match (session.id(), data.is_empty()) {
+            (None, true) => response,
+            (Some(id), true) => {
+                self.store.remove(id);
+                response.set_cookie(Cookie::new(SESSION_COOKIE, "").path("/").max_age(0))
+            }
+            (id, false) => {
+                let id = id.map_or_else(
+                    || Server::generate_request_id() + &Server::generate_request_id(),
+                    str::to_string,
+                );
+                self.store.save(&id, data, self.ttl);
+                response.set_cookie(
+                    Cookie::new(SESSION_COOKIE, &id)
+                        .path("/")
+                        .http_only(true)
+                        .same_site(SameSite::Lax)
+                        .max_age(self.ttl.as_secs())
+                        .signed(),
+                )
+            }
+        }
+    }
+}
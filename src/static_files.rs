@@ -0,0 +1,593 @@
+use std::{
+    fmt,
+    fs::{self, File, Metadata},
+    io::ErrorKind,
+    path::{Component, Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    date::{format_http_date, parse_http_date},
+    query::percent_decode,
+    HttpResponse, Request,
+};
+
+/// How long browsers and caches may reuse a static file, sent as its `Cache-Control` header.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use guest_server::{CacheControl, Server, StaticDir};
+/// use std::time::Duration;
+///
+/// let mut server = Server::new();
+/// // File names carry a content hash, so a changed file gets a new URL
+/// let year = Duration::from_secs(365 * 24 * 60 * 60);
+/// server.static_dir(
+///     "/assets",
+///     StaticDir::new("dist/assets").cache_control(CacheControl::max_age(year).immutable()),
+/// );
+/// // Always revalidated, so a new release is picked up at once
+/// server.static_file_with_cache("/", "dist/index.html", CacheControl::no_cache());
+/// server.listener(8080);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CacheControl {
+    max_age: Option<Duration>, // None for `no-cache`.
+    immutable: bool,
+}
+
+impl CacheControl {
+    /// Lets a file be reused without revalidation for a period, rendered as `max-age` in
+    /// whole seconds.
+    pub fn max_age(max_age: Duration) -> Self {
+        Self {
+            max_age: Some(max_age),
+            immutable: false,
+        }
+    }
+
+    /// Lets a file be stored but requires revalidating it before every reuse, which is cheap
+    /// thanks to `Last-Modified` and 304 Not Modified.
+    pub fn no_cache() -> Self {
+        Self {
+            max_age: None,
+            immutable: false,
+        }
+    }
+
+    /// Adds `immutable`, telling browsers not to revalidate the file while it is fresh, even
+    /// on reload. Only suitable for URLs whose content never changes, e.g. hashed file names.
+    pub fn immutable(mut self) -> Self {
+        self.immutable = true;
+        self
+    }
+}
+
+impl fmt::Display for CacheControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.max_age {
+            Some(max_age) => write!(f, "max-age={}", max_age.as_secs())?,
+            None => write!(f, "no-cache")?,
+        }
+        if self.immutable {
+            write!(f, ", immutable")?;
+        }
+        Ok(())
+    }
+}
+
+/// Serves a file from disk, as registered with `Server::static_file` or `Server::static_dir`.
+///
+/// The body is streamed from the file with its `Content-Type` guessed from the extension and
+/// a `Last-Modified` date from its modification time. A request whose `If-Modified-Since` is
+/// no older than that date gets 304 Not Modified without a body. Both carry the
+/// `Cache-Control` header, if one is configured.
+///
+/// Files compressed at build time are served in place of the original when the client
+/// accepts their encoding: `file.js.br` for `br`, `file.js.gz` for `gzip`, with
+/// `Content-Encoding` set and the original's `Content-Type`. The client's q-values in
+/// `Accept-Encoding` decide between them and the original; on a tie a variant wins over the
+/// original, and `br` over `gzip`. Responses for files with such variants carry
+/// `Vary: Accept-Encoding`, so caches keep the encodings apart.
+///
+/// # Arguments
+/// - 'root' : The canonical directory the file was resolved in, if any. Variants that lead
+///   outside it, through symbolic links, are ignored.
+///
+/// # Returns
+/// The file response, 304, 404 if the file does not exist (or is not a regular file) at
+/// request time, 406 if the client refuses the original (`identity;q=0`) and accepts none
+/// of the variants, or 500 if it cannot be read.
+pub(crate) fn serve_file(
+    path: &Path,
+    root: Option<&Path>,
+    request: &Request,
+    cache_control: Option<&CacheControl>,
+) -> HttpResponse {
+    let (variant, has_variants) = precompressed(path, root, request);
+    let (file_path, encoding) = match &variant {
+        Encoded::Variant(variant_path, encoding) => (variant_path.as_path(), Some(*encoding)),
+        Encoded::Identity => (path, None),
+        Encoded::NotAcceptable => {
+            let response = HttpResponse::status(406);
+            return if has_variants {
+                response.insert_header("Vary", "Accept-Encoding")
+            } else {
+                response
+            };
+        }
+    };
+    let file = match File::open(file_path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return HttpResponse::status(404),
+        Err(e) => {
+            log_error!("Failed to open {}: {}", file_path.display(), e);
+            return HttpResponse::status(500);
+        }
+    };
+    let metadata = match file.metadata() {
+        Ok(metadata) if metadata.is_file() => metadata,
+        Ok(_) => return HttpResponse::status(404),
+        Err(e) => {
+            log_error!("Failed to read {}: {}", file_path.display(), e);
+            return HttpResponse::status(500);
+        }
+    };
+
+    // Headers describing the file, sent with both 200 and 304
+    let last_modified = metadata.modified().ok().map(format_http_date);
+    let mut headers = Vec::new();
+    if let Some(last_modified) = &last_modified {
+        headers.push(("Last-Modified", last_modified.clone()));
+    }
+    if let Some(cache_control) = cache_control {
+        headers.push(("Cache-Control", cache_control.to_string()));
+    }
+    if has_variants {
+        headers.push(("Vary", "Accept-Encoding".to_string()));
+    }
+
+    if let Some(last_modified) = &last_modified {
+        // HTTP dates have whole seconds, so compare them as formatted and parsed
+        let modified = parse_http_date(last_modified);
+        let since = request
+            .header("if-modified-since")
+            .and_then(parse_http_date);
+        if let (Some(modified), Some(since)) = (modified, since) {
+            if modified <= since {
+                let mut response = HttpResponse::status(304);
+                response.headers.remove("Content-Type"); // There is no body to describe.
+                for (name, value) in &headers {
+                    response = response.insert_header(name, value);
+                }
+                return response;
+            }
+        }
+    }
+
+    let length = metadata.len().to_string();
+    let mut response = HttpResponse::stream(200, file)
+        .insert_header("Content-Type", mime_type(path))
+        .insert_header("Content-Length", &length);
+    if let Some(encoding) = encoding {
+        response = response.insert_header("Content-Encoding", encoding);
+    }
+    for (name, value) in &headers {
+        response = response.insert_header(name, value);
+    }
+    response
+}
+
+/// Which representation of a file to send, as negotiated with `Accept-Encoding`.
+enum Encoded {
+    /// A precompressed variant, with its content coding.
+    Variant(PathBuf, &'static str),
+    /// The file itself.
+    Identity,
+    /// Nothing the client accepts.
+    NotAcceptable,
+}
+
+/// Looks for files precompressed next to a file, e.g. `app.js.br` and `app.js.gz`, and picks
+/// the representation the client prefers. Variants outside `root`, if given, do not count.
+///
+/// # Returns
+/// The representation to send, and whether any variant exists at all.
+fn precompressed(path: &Path, root: Option<&Path>, request: &Request) -> (Encoded, bool) {
+    let accepted = request.header("accept-encoding");
+    let mut has_variants = false;
+    let mut best: Option<(PathBuf, &'static str, f32)> = None;
+    for (extension, encoding) in [("br", "br"), ("gz", "gzip")] {
+        let mut variant = path.as_os_str().to_owned();
+        variant.push(".");
+        variant.push(extension);
+        let variant = PathBuf::from(variant);
+        if !variant.is_file() {
+            continue;
+        }
+        // A variant may be a symbolic link leading outside the root
+        if let Some(root) = root {
+            if !variant
+                .canonicalize()
+                .is_ok_and(|resolved| resolved.starts_with(root))
+            {
+                continue;
+            }
+        }
+        has_variants = true;
+        let quality = encoding_quality(accepted, encoding);
+        if quality > 0.0 && best.as_ref().is_none_or(|(_, _, best)| quality > *best) {
+            best = Some((variant, encoding, quality));
+        }
+    }
+
+    let identity = encoding_quality(accepted, "identity");
+    let encoded = match best {
+        Some((variant, encoding, quality)) if quality >= identity => {
+            Encoded::Variant(variant, encoding)
+        }
+        _ if identity > 0.0 => Encoded::Identity,
+        _ => Encoded::NotAcceptable,
+    };
+    (encoded, has_variants)
+}
+
+/// Returns the q-value an `Accept-Encoding` value gives a content coding, from 0 for refused
+/// to 1 for most preferred.
+///
+/// A coding not listed takes the q-value of `*` if that is listed; otherwise it is refused,
+/// except `identity`, which is acceptable unless refused explicitly. Without the header only
+/// `identity` is acceptable, as clients that do not send it may not decode anything else.
+fn encoding_quality(accepted: Option<&str>, encoding: &str) -> f32 {
+    let Some(accepted) = accepted else {
+        return if encoding == "identity" { 1.0 } else { 0.0 };
+    };
+    let mut wildcard = None;
+    for item in accepted.split(',') {
+        let mut params = item.split(';').map(str::trim);
+        let name = params.next().unwrap_or_default();
+        let quality = params
+            .find_map(|param| {
+                let (key, value) = param.split_once('=')?;
+                key.trim().eq_ignore_ascii_case("q").then(|| value.trim())
+            })
+            .map_or(Some(1.0), |q| {
+                q.parse::<f32>().ok().filter(|q| (0.0..=1.0).contains(q))
+            });
+        let Some(quality) = quality else {
+            continue; // An invalid q-value leaves the coding unlisted.
+        };
+        if name.eq_ignore_ascii_case(encoding) {
+            return quality;
+        }
+        if name == "*" {
+            wildcard = Some(quality);
+        }
+    }
+    match wildcard {
+        Some(quality) => quality,
+        None if encoding == "identity" => 1.0,
+        None => 0.0,
+    }
+}
+
+/// Guesses the media type of a file from its extension, falling back to
+/// `application/octet-stream`.
+pub(crate) fn mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase)
+        .unwrap_or_default();
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" | "map" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
+
+/// A directory served under a path prefix, as registered with `Server::static_dir`.
+///
+/// Request paths below the prefix map to files below the directory. Paths are
+/// percent-decoded first, and any that would leave the directory, whether through `..`
+/// segments or symbolic links pointing outside it, get 404. So do hidden entries, whose
+/// names start with a dot, such as `.env` or `.git`. A directory is served through its
+/// `index.html`, or as a generated listing if enabled with `listing`.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use guest_server::{Server, StaticDir};
+///
+/// let mut server = Server::new();
+/// // `/assets/css/site.css` serves `public/css/site.css`
+/// server.static_dir("/assets", StaticDir::new("public"));
+/// server.static_dir("/downloads", StaticDir::new("/srv/downloads").listing(true));
+/// server.listener(8080);
+/// ```
+///
+/// Only what is below the directory, and not hidden, can be reached:
+///
+/// ```rust
+/// # #[cfg(feature = "testing")] {
+/// use guest_server::{Server, StaticDir, TestClient};
+/// use std::fs;
+///
+/// let root = std::env::temp_dir().join(format!("guest-static-{}", std::process::id()));
+/// let public = root.join("public");
+/// fs::create_dir_all(&public).unwrap();
+/// fs::write(root.join("secret"), "secret").unwrap();
+/// fs::write(public.join(".env"), "TOKEN=secret").unwrap();
+/// fs::write(public.join("<b>&.txt"), "tagged").unwrap();
+/// fs::write(public.join("app.js"), "plain").unwrap();
+/// #[cfg(unix)]
+/// {
+///     use std::os::unix::fs::symlink;
+///     symlink(root.join("secret"), public.join("link")).unwrap();
+///     symlink(root.join("secret"), public.join("app.js.gz")).unwrap();
+/// }
+///
+/// let mut server = Server::new();
+/// server.static_dir("/files", StaticDir::new(public.to_str().unwrap()).listing(true));
+/// let handle = server.bind("127.0.0.1:0").unwrap();
+///
+/// let client = TestClient::new(handle.local_addrs()[0]);
+/// for path in ["/files/%2e%2e/secret", "/files/..%2fsecret", "/files/.env", "/files/link"] {
+///     assert_eq!(client.get(path).unwrap().status_code, 404, "{}", path);
+/// }
+/// assert_eq!(client.get("/files/%3Cb%3E%26.txt").unwrap().text(), "tagged");
+/// let gzip = [("Accept-Encoding", "gzip")];
+/// let response = client.request("GET", "/files/app.js", &gzip, b"").unwrap();
+/// assert_eq!(response.text(), "plain");
+/// assert!(!response.headers.contains_key("content-encoding"));
+///
+/// let listing = client.get("/files/").unwrap().text();
+/// assert!(listing.contains("<a href=\"/files/%3Cb%3E%26.txt\">&lt;b&gt;&amp;.txt</a>"));
+/// assert!(!listing.contains("<b>"));
+/// assert!(!listing.contains(".env"));
+/// assert!(!listing.contains("link"));
+///
+/// handle.shutdown();
+/// handle.join();
+/// fs::remove_dir_all(&root).unwrap();
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct StaticDir {
+    root: PathBuf,
+    listing: bool,
+    cache_control: Option<CacheControl>,
+    pub(crate) prefix: String, // Set when registered with `Server::static_dir`.
+}
+
+impl StaticDir {
+    /// Creates a directory to serve, relative to the working directory or absolute, without
+    /// listings.
+    pub fn new(dir: &str) -> Self {
+        Self {
+            root: PathBuf::from(dir),
+            listing: false,
+            cache_control: None,
+            prefix: String::new(),
+        }
+    }
+
+    /// Sets whether a directory without an `index.html` is answered with an HTML page listing
+    /// its entries, with links, sizes and modification times, as nginx's `autoindex` does.
+    ///
+    /// Disabled by default, as listings reveal every file in the directory, including ones
+    /// nothing links to. Hidden entries, whose names start with a dot, are never listed, as
+    /// they are never served.
+    pub fn listing(mut self, enabled: bool) -> Self {
+        self.listing = enabled;
+        self
+    }
+
+    /// Sets the `Cache-Control` header sent with the files, including index files. None is
+    /// sent by default, leaving browsers to guess how long to reuse files.
+    pub fn cache_control(mut self, cache_control: CacheControl) -> Self {
+        self.cache_control = Some(cache_control);
+        self
+    }
+
+    /// Reports whether a request path falls under the prefix, on a segment boundary.
+    pub(crate) fn matches(&self, path: &str) -> bool {
+        match path.strip_prefix(&self.prefix) {
+            Some(rest) => rest.is_empty() || rest.starts_with('/') || self.prefix.ends_with('/'),
+            None => false,
+        }
+    }
+
+    /// Serves the file or directory a request path under the prefix maps to.
+    ///
+    /// # Parameters
+    /// - 'request' : The request.
+    /// - 'normalized' : Whether trailing slashes were removed from the path, in which case
+    ///   redirecting to add one would loop.
+    ///
+    /// # Returns
+    /// The file, the directory's index or listing, a redirect adding the trailing slash a
+    /// directory URL needs for relative links to work, or 404.
+    pub(crate) fn serve(&self, request: &Request, normalized: bool) -> HttpResponse {
+        let Some((path, root)) = self.resolve(&request.path) else {
+            return HttpResponse::status(404);
+        };
+        if !path.is_dir() {
+            return serve_file(&path, Some(&root), request, self.cache_control.as_ref());
+        }
+
+        if !request.path.ends_with('/') && !normalized {
+            let mut location = format!("{}/", request.path);
+            if !request.query.as_str().is_empty() {
+                location.push('?');
+                location.push_str(request.query.as_str());
+            }
+            return HttpResponse::redirect(301, &location);
+        }
+        let index = path.join("index.html");
+        if index.is_file() {
+            return match index.canonicalize() {
+                Ok(index) if index.starts_with(&root) => {
+                    serve_file(&index, Some(&root), request, self.cache_control.as_ref())
+                }
+                _ => HttpResponse::status(404),
+            };
+        }
+        if self.listing {
+            return self.list(&path, request);
+        }
+        HttpResponse::status(404)
+    }
+
+    /// Maps a request path to a path below the root, refusing any that would leave it.
+    ///
+    /// # Returns
+    /// The canonical path and the canonical root, or `None` if refused.
+    fn resolve(&self, request_path: &str) -> Option<(PathBuf, PathBuf)> {
+        let rest = request_path.strip_prefix(&self.prefix)?;
+        let mut path = self.root.clone();
+        for segment in rest.split('/').filter(|segment| !segment.is_empty()) {
+            let segment = percent_decode(segment)?;
+            // Each segment must name one entry that is not hidden: no `..`, separators, or
+            // drive prefixes
+            let mut components = Path::new(&segment).components();
+            match (components.next(), components.next()) {
+                (Some(Component::Normal(name)), None)
+                    if !segment.contains(['\\', '\0']) && !segment.starts_with('.') =>
+                {
+                    path.push(name)
+                }
+                _ => return None,
+            }
+        }
+
+        // Symbolic links may still lead outside the root
+        let root = self.root.canonicalize().ok()?;
+        let resolved = path.canonicalize().ok()?;
+        resolved.starts_with(&root).then_some((resolved, root))
+    }
+
+    /// Renders the HTML listing of a directory.
+    fn list(&self, dir: &Path, request: &Request) -> HttpResponse {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log_error!("Failed to list {}: {}", dir.display(), e);
+                return HttpResponse::status(500);
+            }
+        };
+        let root = self.root.canonicalize().ok();
+        let mut entries: Vec<(String, Option<Metadata>)> = entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                // Leave out symbolic links leading outside the root, which `resolve` refuses
+                let target = entry.path().canonicalize().ok()?;
+                if name.starts_with('.') || !target.starts_with(root.as_ref()?) {
+                    return None;
+                }
+                // Follow symbolic links, so a link to a directory is listed as one
+                Some((name, fs::metadata(target).ok()))
+            })
+            .collect();
+        // Directories first, then by name
+        entries.sort_by_key(|(name, metadata)| {
+            (
+                !metadata.as_ref().is_some_and(Metadata::is_dir),
+                name.clone(),
+            )
+        });
+
+        let title = format!("Index of {}", escape_html(&request.path));
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{0}</title></head>\n\
+             <body>\n<h1>{0}</h1>\n<table>\n\
+             <tr><th>Name</th><th>Size</th><th>Last modified</th></tr>\n",
+            title
+        );
+        // Links are absolute, so they work whether or not the path ends with a slash
+        let base = escape_html(request.path.trim_end_matches('/'));
+        if request.path.trim_end_matches('/') != self.prefix.trim_end_matches('/') {
+            let parent = &base[..base.rfind('/').unwrap_or_default()];
+            html.push_str(&format!(
+                "<tr><td><a href=\"{}/\">../</a></td><td></td><td></td></tr>\n",
+                parent
+            ));
+        }
+        for (name, metadata) in entries {
+            let is_dir = metadata.as_ref().is_some_and(Metadata::is_dir);
+            let slash = if is_dir { "/" } else { "" };
+            let size = match &metadata {
+                Some(metadata) if !is_dir => metadata.len().to_string(),
+                _ => "-".to_string(),
+            };
+            let modified = metadata
+                .and_then(|metadata| metadata.modified().ok())
+                .map_or_else(|| "-".to_string(), format_http_date);
+            html.push_str(&format!(
+                "<tr><td><a href=\"{}/{}{}\">{}{}</a></td><td>{}</td><td>{}</td></tr>\n",
+                base,
+                percent_encode(&name),
+                slash,
+                escape_html(&name),
+                slash,
+                size,
+                modified
+            ));
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+        HttpResponse::with_content_type(200, "text/html; charset=utf-8", Some(html))
+    }
+}
+
+/// Escapes the characters that are special in HTML text and attribute values.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Percent-encodes a file name for use as a relative URL path segment.
+fn percent_encode(name: &str) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
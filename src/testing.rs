@@ -0,0 +1,154 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Error, ErrorKind, Read, Write},
+    net::{SocketAddr, TcpStream},
+};
+
+/// A minimal HTTP client for end-to-end tests of a running server.
+///
+/// Each request opens a new connection, sends `Connection: close` and reads the response to
+/// the end. Requires the `testing` feature.
+///
+/// # Example
+///
+/// ```rust
+/// use guest_server::{Server, TestClient};
+///
+/// let mut server = Server::new();
+/// server.get("/hello", |_| "Hello, World!");
+/// let handle = server.bind("127.0.0.1:0").unwrap();
+///
+/// let client = TestClient::new(handle.local_addrs()[0]);
+/// let response = client.get("/hello").unwrap();
+/// assert_eq!(response.status_code, 200);
+/// assert_eq!(response.headers["content-type"], "text/plain");
+/// assert_eq!(response.text(), "Hello, World!");
+/// assert_eq!(client.get("/missing").unwrap().status_code, 404);
+///
+/// handle.shutdown();
+/// handle.join();
+/// ```
+#[derive(Clone, Debug)]
+pub struct TestClient {
+    addr: SocketAddr,
+}
+
+/// A response received by a `TestClient`.
+#[derive(Clone, Debug)]
+pub struct TestResponse {
+    pub status_code: u16,
+    /// The response headers, keyed by lowercase header name. A repeated header keeps its
+    /// last value.
+    pub headers: HashMap<String, String>,
+    /// The response body, with any chunked transfer encoding removed.
+    pub body: Vec<u8>,
+}
+
+impl TestClient {
+    /// Creates a client sending requests to a server address, e.g. one returned by
+    /// `ServerHandle::local_addrs`.
+    pub fn new(addr: SocketAddr) -> Self {
+        Self { addr }
+    }
+
+    /// Sends a GET request without headers.
+    pub fn get(&self, path: &str) -> Result<TestResponse, Error> {
+        self.request("GET", path, &[], &[])
+    }
+
+    /// Sends a POST request with a body and no other headers.
+    pub fn post(&self, path: &str, body: &[u8]) -> Result<TestResponse, Error> {
+        self.request("POST", path, &[], body)
+    }
+
+    /// Sends a request and waits for the complete response.
+    ///
+    /// # Parameters
+    /// - 'method' : The request method, e.g. `PATCH`.
+    /// - 'path' : The request target, including any query string.
+    /// - 'headers' : Extra request headers. `Host`, `Connection` and `Content-Length` are
+    ///   always sent.
+    /// - 'body' : The request body, empty for none.
+    ///
+    /// # Returns
+    /// The parsed response, or an error if the connection failed or the response was not
+    /// valid HTTP.
+    pub fn request(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> Result<TestResponse, Error> {
+        let mut stream = TcpStream::connect(self.addr)?;
+        let mut head = format!("{} {} HTTP/1.1\r\nHost: {}\r\n", method, path, self.addr);
+        for (name, value) in headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str(&format!(
+            "Content-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        ));
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(body)?;
+
+        let mut reader = BufReader::new(stream);
+        let invalid = || Error::new(ErrorKind::InvalidData, "Malformed response");
+        // Interim (1xx) responses such as `103 Early Hints` are skipped for the final one
+        let (status_code, headers) = loop {
+            let mut status_line = String::new();
+            reader.read_line(&mut status_line)?;
+            let status_code: u16 = status_line
+                .split_whitespace()
+                .nth(1)
+                .and_then(|code| code.parse().ok())
+                .ok_or_else(invalid)?;
+
+            let mut headers = HashMap::new();
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line)? == 0 {
+                    return Err(invalid());
+                }
+                if line.trim_end().is_empty() {
+                    break;
+                }
+                let (name, value) = line.split_once(':').ok_or_else(invalid)?;
+                headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+            }
+            if !(100..=199).contains(&status_code) {
+                break (status_code, headers);
+            }
+        };
+
+        let mut body = Vec::new();
+        if headers.get("transfer-encoding").map(String::as_str) == Some("chunked") {
+            loop {
+                let mut size = String::new();
+                reader.read_line(&mut size)?;
+                let size = usize::from_str_radix(size.trim(), 16).map_err(|_| invalid())?;
+                let mut chunk = vec![0; size + 2]; // The chunk and its CRLF.
+                reader.read_exact(&mut chunk)?;
+                if size == 0 {
+                    break;
+                }
+                body.extend_from_slice(&chunk[..size]);
+            }
+        } else {
+            reader.read_to_end(&mut body)?;
+        }
+
+        Ok(TestResponse {
+            status_code,
+            headers,
+            body,
+        })
+    }
+}
+
+impl TestResponse {
+    /// Returns the body as text, replacing bytes that are not UTF-8.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
@@ -0,0 +1,347 @@
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Cursor, ErrorKind, Read, Seek, Take, Write},
+    path::PathBuf,
+    process,
+    sync::Arc,
+};
+
+use crate::{response::IntoResponse, HttpMethod, HttpResponse, Request, Server};
+
+/// The most unread body bytes discarded to keep a connection open after a body was refused
+/// or left unread; the connection is closed instead when more remain.
+const DRAIN_LIMIT: u64 = 64 * 1024;
+
+/// Reads a request body and runs the handler, with the sink type erased.
+type Receive = Arc<dyn Fn(&Request, &mut Take<&mut dyn Read>) -> HttpResponse + Send + Sync>;
+
+/// A route whose request body is streamed into a sink, read by the handler or spooled
+/// instead of being buffered in the request, as registered with `Server::post_streaming`,
+/// `Server::post_lazy` or `Server::post_spooled`.
+#[derive(Clone)]
+pub(crate) struct Upload {
+    pub(crate) method: HttpMethod,
+    pub(crate) path: String,
+    limit: u64, // Largest body accepted, in bytes.
+    receive: Receive,
+}
+
+impl Upload {
+    /// Creates an upload route.
+    ///
+    /// # Parameters
+    /// - 'method' : The method the route answers.
+    /// - 'path' : The exact path the route answers.
+    /// - 'limit' : The largest body accepted, in bytes.
+    /// - 'sink' : Opens the writer the body is copied into, or refuses the request with a
+    ///   response.
+    /// - 'handler' : Builds the response once the whole body has been written, taking the sink
+    ///   back, e.g. to close or rename a file.
+    pub(crate) fn new<W, S, F, T>(
+        method: HttpMethod,
+        path: &str,
+        limit: u64,
+        sink: S,
+        handler: F,
+    ) -> Self
+    where
+        W: Write,
+        S: Fn(&Request) -> Result<W, HttpResponse> + Send + Sync + 'static,
+        F: Fn(&Request, W) -> T + Send + Sync + 'static,
+        T: IntoResponse,
+    {
+        let receive = move |request: &Request, body: &mut Take<&mut dyn Read>| {
+            let mut writer = match sink(request) {
+                Ok(writer) => writer,
+                Err(response) => return response,
+            };
+            if let Err(status_code) = copy(body, &mut writer) {
+                return HttpResponse::new(status_code, None);
+            }
+            handler(request, writer).into_response()
+        };
+        Self {
+            method,
+            path: path.to_string(),
+            limit,
+            receive: Arc::new(receive),
+        }
+    }
+
+    /// Creates a route whose handler reads the body itself, as much of it as it wants.
+    ///
+    /// # Parameters
+    /// - 'method' : The method the route answers.
+    /// - 'path' : The exact path the route answers.
+    /// - 'handler' : Builds the response, given the body to read on demand.
+    pub(crate) fn lazy<F, T>(method: HttpMethod, path: &str, handler: F) -> Self
+    where
+        F: Fn(&Request, BodyReader) -> T + Send + Sync + 'static,
+        T: IntoResponse,
+    {
+        let receive = move |request: &Request, body: &mut Take<&mut dyn Read>| {
+            handler(request, BodyReader::new(body)).into_response()
+        };
+        Self {
+            method,
+            path: path.to_string(),
+            limit: u64::MAX,
+            receive: Arc::new(receive),
+        }
+    }
+
+    /// Creates a route that buffers bodies up to a size in memory and spools larger ones to
+    /// a temporary file, removed once the handler returns.
+    ///
+    /// # Parameters
+    /// - 'method' : The method the route answers.
+    /// - 'path' : The exact path the route answers.
+    /// - 'threshold' : The largest body kept in memory, in bytes.
+    /// - 'limit' : The largest body accepted, in bytes.
+    /// - 'handler' : Builds the response, given the body.
+    pub(crate) fn spooled<F, T>(
+        method: HttpMethod,
+        path: &str,
+        threshold: u64,
+        limit: u64,
+        handler: F,
+    ) -> Self
+    where
+        F: Fn(&Request, SpooledBody) -> T + Send + Sync + 'static,
+        T: IntoResponse,
+    {
+        let receive = move |request: &Request, body: &mut Take<&mut dyn Read>| {
+            if body.limit() <= threshold {
+                let mut buffer = Vec::new();
+                if let Err(status_code) = copy(body, &mut buffer) {
+                    return HttpResponse::status(status_code);
+                }
+                return handler(request, SpooledBody::Memory(buffer)).into_response();
+            }
+
+            let (mut file, temp) = match TempFile::create() {
+                Ok(created) => created,
+                Err(e) => {
+                    log_error!(
+                        "Failed to create a temporary file for a request body: {}",
+                        e
+                    );
+                    return HttpResponse::status(500);
+                }
+            };
+            if let Err(status_code) = copy(body, &mut file) {
+                return HttpResponse::status(status_code);
+            }
+            if let Err(e) = file.rewind() {
+                log_error!("Failed to read back request body: {}", e);
+                return HttpResponse::status(500);
+            }
+            let response = handler(request, SpooledBody::File(file)).into_response();
+            drop(temp);
+            response
+        };
+        Self {
+            method,
+            path: path.to_string(),
+            limit,
+            receive: Arc::new(receive),
+        }
+    }
+
+    /// Reports whether the upload route answers a request.
+    pub(crate) fn matches(&self, request: &Request) -> bool {
+        request.method == self.method && request.path == self.path
+    }
+
+    /// Streams a request body, delimited by its `Content-Length`, into the sink and runs the
+    /// handler.
+    ///
+    /// # Parameters
+    /// - 'request' : The request, whose body has not been read.
+    /// - 'body' : The source of the body, positioned at its first byte.
+    ///
+    /// # Returns
+    /// The handler's response, 413 if the body is larger than the limit, 400 if it ends early,
+    /// or 500 if the sink fails.
+    ///
+    /// The next request on the connection starts after the body, so whatever is left unread,
+    /// e.g. because the body was refused or the sink was not opened, is read and discarded
+    /// up to `DRAIN_LIMIT` bytes. If more is left, the response closes the connection instead.
+    pub(crate) fn receive(&self, request: &Request, body: &mut dyn Read) -> HttpResponse {
+        let length = request.content_length();
+        let mut body = Read::take(body, length);
+        let response = if length > self.limit {
+            HttpResponse::status(413)
+        } else {
+            (self.receive)(request, &mut body)
+        };
+
+        if drain(&mut body) {
+            response
+        } else {
+            response.close_connection()
+        }
+    }
+}
+
+/// A request body read on demand, as handed to the handlers of `Server::post_lazy`.
+///
+/// Reads the body as it arrives, up to the request's `Content-Length`, and then reports its
+/// end. A body the client stops sending early fails to read with `UnexpectedEof` rather than
+/// ending short.
+pub struct BodyReader<'a> {
+    body: &'a mut dyn Read,
+    length: u64,
+    remaining: u64,
+}
+
+impl<'a> BodyReader<'a> {
+    fn new(body: &'a mut Take<&mut dyn Read>) -> Self {
+        let length = body.limit();
+        Self {
+            body,
+            length,
+            remaining: length,
+        }
+    }
+
+    /// Returns the length of the whole body, as sent in `Content-Length`.
+    pub fn content_length(&self) -> u64 {
+        self.length
+    }
+
+    /// Returns the number of body bytes not read yet.
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+}
+
+impl Read for BodyReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+        let read = self.body.read(buf)?;
+        if read == 0 {
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                format!("Request body ended {} bytes early", self.remaining),
+            ));
+        }
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+/// A request body held in memory, or in a temporary file when it is large, as handed to the
+/// handlers of `Server::post_spooled`.
+#[derive(Debug)]
+pub enum SpooledBody {
+    /// A body no larger than the threshold.
+    Memory(Vec<u8>),
+    /// A larger body, in a temporary file positioned at its start. The file is removed once
+    /// the handler returns, so copy or move it elsewhere to keep it.
+    File(File),
+}
+
+impl SpooledBody {
+    /// Returns a reader over the body, wherever it is held.
+    pub fn into_reader(self) -> Box<dyn Read + Send> {
+        match self {
+            SpooledBody::Memory(bytes) => Box::new(Cursor::new(bytes)),
+            SpooledBody::File(file) => Box::new(file),
+        }
+    }
+}
+
+/// Removes a temporary file holding a request body when dropped.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    /// Creates an empty file under a new, unpredictable name in the system's temporary
+    /// directory, so other users of the directory can neither guess nor claim it first. On
+    /// Unix only the owner may read or write it (mode `0600`), so they cannot read it either.
+    fn create() -> io::Result<(File, TempFile)> {
+        let mut attempts = 0;
+        loop {
+            let name = format!(
+                "guest-body-{}-{}",
+                process::id(),
+                Server::generate_request_id()
+            );
+            let path = env::temp_dir().join(name);
+            let mut options = OpenOptions::new();
+            options.read(true).write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            let opened = options.open(&path);
+            match opened {
+                Ok(file) => return Ok((file, TempFile(path))),
+                // Taken by a file not made here; another random name will do
+                Err(e) if e.kind() == ErrorKind::AlreadyExists && attempts < 3 => attempts += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.0) {
+            log_error!("Failed to remove {}: {}", self.0.display(), e);
+        }
+    }
+}
+
+/// Reads and discards the rest of a body, if it is no longer than `DRAIN_LIMIT`.
+///
+/// # Returns
+/// `true` if the whole body has been read, so the connection can serve another request.
+fn drain(body: &mut Take<&mut dyn Read>) -> bool {
+    if body.limit() == 0 {
+        return true;
+    }
+    if body.limit() > DRAIN_LIMIT {
+        return false;
+    }
+    match io::copy(body, &mut io::sink()) {
+        Ok(_) => body.limit() == 0,
+        Err(e) => {
+            log_debug!("Failed to drain request body: {}", e);
+            false
+        }
+    }
+}
+
+/// Copies a body into a sink as it arrives, without buffering it whole.
+///
+/// # Returns
+/// `Err` with the status code to answer if the body ends early or cannot be read (400), or if
+/// the sink fails (500).
+fn copy<W: Write>(body: &mut Take<&mut dyn Read>, sink: &mut W) -> Result<(), u16> {
+    let mut buffer = [0; 8192];
+    loop {
+        let read = match body.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => {
+                log_debug!("Failed to read request body: {}", e);
+                return Err(400);
+            }
+        };
+        if let Err(e) = sink.write_all(&buffer[..read]) {
+            log_error!("Failed to write request body: {}", e);
+            return Err(500);
+        }
+    }
+    if body.limit() > 0 {
+        log_debug!("Request body ended {} bytes early", body.limit());
+        return Err(400);
+    }
+    sink.flush().map_err(|e| {
+        log_error!("Failed to write request body: {}", e);
+        500
+    })
+}